@@ -32,33 +32,61 @@ SelectParams("regtest")
 class InMemoryReceiverPersister(ReceiverPersister):
     def __init__(self):
         super().__init__()
-        self.receivers = {}
+        self.sessions = {}
 
-    def save(self, receiver: Receiver) -> ReceiverToken:
-        self.receivers[str(receiver.key())] = receiver.to_json()
+    def save(self, session: ReceiverSession) -> ReceiverToken:
+        self.sessions[str(session.key())] = session.to_json()
 
-        return receiver.key()
+        return session.key()
 
-    def load(self, token: ReceiverToken) -> Receiver:
+    def load(self, token: ReceiverToken) -> ReceiverSession:
         token = str(token)
-        if token not in self.receivers.keys():
-            raise ValueError(f"Token not found: {token}")
-        return Receiver.from_json(self.receivers[token])
+        if token not in self.sessions.keys():
+            raise PersistenceError.NotFound(token=token)
+        return ReceiverSession.from_json(self.sessions[token])
+
+    def list(self) -> list[ReceiverToken]:
+        return [ReceiverSession.from_json(json).key() for json in self.sessions.values()]
+
+    def delete(self, token: ReceiverToken):
+        self.sessions.pop(str(token), None)
+
+    def metadata(self, token: ReceiverToken) -> SessionMetadata:
+        return SessionMetadata(
+            token=str(token),
+            created_at=0,
+            expires_at=self.load(token).receiver().expires_at(),
+            state=SessionState.ACTIVE,
+        )
 
 class InMemorySenderPersister(SenderPersister):
     def __init__(self):
         super().__init__()
-        self.senders = {}
+        self.sessions = {}
 
-    def save(self, sender: Sender) -> SenderToken:
-        self.senders[str(sender.key())] = sender.to_json()
-        return sender.key()
+    def save(self, session: SenderSession) -> SenderToken:
+        self.sessions[str(session.key())] = session.to_json()
+        return session.key()
 
-    def load(self, token: SenderToken) -> Sender:
+    def load(self, token: SenderToken) -> SenderSession:
         token = str(token)
-        if token not in self.senders.keys():
-            raise ValueError(f"Token not found: {token}")
-        return Sender.from_json(self.senders[token])
+        if token not in self.sessions.keys():
+            raise PersistenceError.NotFound(token=token)
+        return SenderSession.from_json(self.sessions[token])
+
+    def list(self) -> list[SenderToken]:
+        return [SenderSession.from_json(json).key() for json in self.sessions.values()]
+
+    def delete(self, token: SenderToken):
+        self.sessions.pop(str(token), None)
+
+    def metadata(self, token: SenderToken) -> SessionMetadata:
+        return SessionMetadata(
+            token=str(token),
+            created_at=0,
+            expires_at=self.load(token).sender().expires_at(),
+            state=SessionState.ACTIVE,
+        )
 
 class TestPayjoin(unittest.IsolatedAsyncioTestCase):
     @classmethod
@@ -103,7 +131,7 @@ class TestPayjoin(unittest.IsolatedAsyncioTestCase):
             # Create a funded PSBT (not broadcasted) to address with amount given in the pj_uri
             pj_uri = session.pj_uri()
             psbt = build_sweep_psbt(self.sender, pj_uri)
-            new_sender = SenderBuilder(psbt, pj_uri).build_recommended(1000)
+            new_sender = SenderBuilder(psbt, pj_uri).build_recommended(bitcoinffi.FeeRate.from_sat_per_kwu(1000))
             persister = InMemorySenderPersister()
             token = new_sender.persist(persister)
             req_ctx: Sender = Sender.load(token, persister)
@@ -173,7 +201,11 @@ def handle_directory_payjoin_proposal(receiver: Proxy, proposal: UncheckedPropos
     wants_outputs = outputs_unknown.identify_receiver_outputs(IsScriptOwnedCallback(receiver))
     wants_inputs = wants_outputs.commit_outputs()
     provisional_proposal = wants_inputs.contribute_inputs(get_inputs(receiver)).commit_inputs()
-    return provisional_proposal.finalize_proposal(ProcessPsbtCallback(receiver), 1, 10)
+    return provisional_proposal.finalize_proposal(
+        ProcessPsbtCallback(receiver),
+        bitcoinffi.FeeRate.from_sat_per_vb(1),
+        bitcoinffi.FeeRate.from_sat_per_vb(10),
+    )
 
 def build_sweep_psbt(sender: Proxy, pj_uri: PjUri) -> bitcoinffi.Psbt:
     outputs = {}
@@ -1,3 +1,5 @@
+import tempfile
+import time
 import unittest
 import payjoin as payjoin
 import payjoin.bitcoin
@@ -47,20 +49,35 @@ class OutputOwnershipCallback(payjoin.IsOutputKnown):
     def callback(self, outpoint: payjoin.bitcoin.OutPoint):
         return False
 
+def session_metadata(token, expires_at) -> payjoin.SessionMetadata:
+    now = int(time.time())
+    expired = expires_at is not None and expires_at <= now
+    state = payjoin.SessionState.EXPIRED if expired else payjoin.SessionState.ACTIVE
+    return payjoin.SessionMetadata(token=str(token), created_at=now, expires_at=expires_at, state=state)
+
 class InMemoryReceiverPersister(payjoin.payjoin_ffi.ReceiverPersister):
     def __init__(self):
-        self.receivers = {}
+        self.sessions = {}
 
-    def save(self, receiver: payjoin.Receiver) -> payjoin.ReceiverToken:
-        self.receivers[str(receiver.key())] = receiver.to_json()
+    def save(self, session: payjoin.ReceiverSession) -> payjoin.ReceiverToken:
+        self.sessions[str(session.key())] = session.to_json()
 
-        return receiver.key()
+        return session.key()
 
-    def load(self, token: payjoin.ReceiverToken) -> payjoin.Receiver:
+    def load(self, token: payjoin.ReceiverToken) -> payjoin.ReceiverSession:
         token = str(token)
-        if token not in self.receivers.keys():
-            raise ValueError(f"Token not found: {token}")
-        return payjoin.Receiver.from_json(self.receivers[token])
+        if token not in self.sessions.keys():
+            raise payjoin.PersistenceError.NotFound(token=token)
+        return payjoin.ReceiverSession.from_json(self.sessions[token])
+
+    def list(self) -> list[payjoin.ReceiverToken]:
+        return [payjoin.ReceiverSession.from_json(json).key() for json in self.sessions.values()]
+
+    def delete(self, token: payjoin.ReceiverToken):
+        self.sessions.pop(str(token), None)
+
+    def metadata(self, token: payjoin.ReceiverToken) -> payjoin.SessionMetadata:
+        return session_metadata(token, self.load(token).receiver().expires_at())
 
  
 class TestRecieverPersistence(unittest.TestCase):
@@ -78,17 +95,26 @@ class TestRecieverPersistence(unittest.TestCase):
 
 class InMemorySenderPersister(payjoin.payjoin_ffi.SenderPersister):
     def __init__(self):
-        self.senders = {}
+        self.sessions = {}
 
-    def save(self, sender: payjoin.Sender) -> payjoin.SenderToken:
-        self.senders[str(sender.key())] = sender.to_json()
-        return sender.key()
+    def save(self, session: payjoin.SenderSession) -> payjoin.SenderToken:
+        self.sessions[str(session.key())] = session.to_json()
+        return session.key()
     
-    def load(self, token: payjoin.SenderToken) -> payjoin.Sender:
+    def load(self, token: payjoin.SenderToken) -> payjoin.SenderSession:
         token = str(token)
-        if token not in self.senders.keys():
-            raise ValueError(f"Token not found: {token}")
-        return payjoin.Sender.from_json(self.senders[token])
+        if token not in self.sessions.keys():
+            raise payjoin.PersistenceError.NotFound(token=token)
+        return payjoin.SenderSession.from_json(self.sessions[token])
+
+    def list(self) -> list[payjoin.SenderToken]:
+        return [payjoin.SenderSession.from_json(json).key() for json in self.sessions.values()]
+
+    def delete(self, token: payjoin.SenderToken):
+        self.sessions.pop(str(token), None)
+
+    def metadata(self, token: payjoin.SenderToken) -> payjoin.SessionMetadata:
+        return session_metadata(token, self.load(token).sender().expires_at())
     
 class TestSenderPersistence(unittest.TestCase):
     def test_sender_persistence(self):
@@ -107,9 +133,30 @@ class TestSenderPersistence(unittest.TestCase):
 
         persister = InMemorySenderPersister()
         psbt = "cHNidP8BAHMCAAAAAY8nutGgJdyYGXWiBEb45Hoe9lWGbkxh/6bNiOJdCDuDAAAAAAD+////AtyVuAUAAAAAF6kUHehJ8GnSdBUOOv6ujXLrWmsJRDCHgIQeAAAAAAAXqRR3QJbbz0hnQ8IvQ0fptGn+votneofTAAAAAAEBIKgb1wUAAAAAF6kU3k4ekGHKWRNbA1rV5tR5kEVDVNCHAQcXFgAUx4pFclNVgo1WWAdN1SYNX8tphTABCGsCRzBEAiB8Q+A6dep+Rz92vhy26lT0AjZn4PRLi8Bf9qoB/CMk0wIgP/Rj2PWZ3gEjUkTlhDRNAQ0gXwTO7t9n+V14pZ6oljUBIQMVmsAaoNWHVMS02LfTSe0e388LNitPa1UQZyOihY+FFgABABYAFEb2Giu6c4KO5YW0pfw3lGp9jMUUAAA="
-        new_sender = payjoin.SenderBuilder(psbt, uri).build_recommended(1000)
+        new_sender = payjoin.SenderBuilder(psbt, uri).build_recommended(payjoin.bitcoin.FeeRate.from_sat_per_kwu(1000))
         token = new_sender.persist(persister)
         payjoin.Sender.load(token, persister)
-            
+
+class TestFilePersister(unittest.TestCase):
+    def test_receiver_file_persistence(self):
+        with tempfile.TemporaryDirectory() as dir:
+            persister = payjoin.FilePersister(dir).receiver_persister()
+            address = payjoin.bitcoin.Address("tb1q6d3a2w975yny0asuvd9a67ner4nks58ff0q8g4", payjoin.bitcoin.Network.SIGNET)
+            new_receiver = payjoin.NewReceiver(
+                address,
+                "https://example.com",
+                payjoin.OhttpKeys.from_string("OH1QYPM5JXYNS754Y4R45QWE336QFX6ZR8DQGVQCULVZTV20TFVEYDMFQC"),
+                None
+            )
+            token = new_receiver.persist(persister)
+            reopened = payjoin.FilePersister(dir).receiver_persister()
+            receiver = payjoin.Receiver.load(token, reopened)
+            self.assertEqual(str(receiver.key()), str(token))
+
+            self.assertEqual([str(t) for t in reopened.list()], [str(token)])
+            self.assertEqual(reopened.metadata(token).state, payjoin.SessionState.ACTIVE)
+            reopened.delete(token)
+            self.assertEqual(reopened.list(), [])
+
 if __name__ == "__main__":
     unittest.main()
@@ -54,3 +54,32 @@ impl From<PsbtInput> for bitcoin::psbt::Input {
         }
     }
 }
+
+// The wrappers are the same types with and without the `uniffi` feature, so these run under both
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fee_rate_units_agree() {
+        assert_eq!(FeeRate::from_sat_per_vb(1).unwrap().to_sat_per_kwu(), 250);
+        assert_eq!(FeeRate::from_sat_per_kwu(250).to_sat_per_vb_floor(), 1);
+        assert_eq!(FeeRate::from_sat_per_kwu(251).to_sat_per_vb_ceil(), 2);
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        assert!(FeeRate::from_sat_per_vb(u64::MAX).is_err());
+        assert!(Amount::from_btc(-1.0).is_err());
+        assert!(Amount::from_btc(f64::MAX).is_err());
+    }
+
+    #[test]
+    fn conversions_to_payjoin_keep_the_value() {
+        let fee_rate: bitcoin::FeeRate = FeeRate::from_sat_per_vb(2).unwrap().into();
+        assert_eq!(fee_rate.to_sat_per_kwu(), 500);
+        let amount: bitcoin::Amount = Amount::from_sat(1_000).into();
+        assert_eq!(amount.to_sat(), 1_000);
+        assert_eq!(Amount::from(amount).to_sat(), 1_000);
+    }
+}
@@ -1,8 +1,45 @@
+use std::sync::Arc;
+
+use crate::ohttp::ClientResponseError;
+use crate::send::V2PostContextError;
+
 #[derive(Debug, thiserror::Error)]
 #[error("Error de/serializing JSON object: {0}")]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
 pub struct SerdeJsonError(#[from] serde_json::Error);
 
+/// Error serializing a single-use context with `to_json`.
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
+pub enum ContextSerializationError {
+    /// The [`crate::ClientResponse`] was already used, so there is nothing left to save
+    #[error("{0}")]
+    ClientResponseUsed(Arc<ClientResponseError>),
+    /// The [`crate::send::V2PostContext`] was already used, so there is nothing left to save
+    #[error("{0}")]
+    V2PostContextUsed(Arc<V2PostContextError>),
+    #[error("{0}")]
+    Json(Arc<SerdeJsonError>),
+}
+
+impl From<ClientResponseError> for ContextSerializationError {
+    fn from(value: ClientResponseError) -> Self {
+        Self::ClientResponseUsed(Arc::new(value))
+    }
+}
+
+impl From<V2PostContextError> for ContextSerializationError {
+    fn from(value: V2PostContextError) -> Self {
+        Self::V2PostContextUsed(Arc::new(value))
+    }
+}
+
+impl From<serde_json::Error> for ContextSerializationError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(Arc::new(value.into()))
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
 pub enum ForeignError {
@@ -16,3 +53,49 @@ impl From<uniffi::UnexpectedUniFFICallbackError> for ForeignError {
         Self::InternalError("Unexpected Uniffi callback error".to_string())
     }
 }
+
+/// Error that may occur when loading a session from a persister.
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
+pub enum PersistenceError {
+    /// No session is stored under the given token.
+    #[error("Session not found: {token}")]
+    NotFound { token: String },
+    /// A session is stored under the given token but could not be deserialized.
+    #[error("Error deserializing session: {msg}")]
+    Deserialization { msg: String },
+    /// The storage backend failed.
+    #[error("Persistence backend error: {msg}")]
+    Backend { msg: String },
+}
+
+impl From<serde_json::Error> for PersistenceError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Deserialization { msg: value.to_string() }
+    }
+}
+
+impl From<std::io::Error> for PersistenceError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Backend { msg: value.to_string() }
+    }
+}
+
+impl From<ForeignError> for PersistenceError {
+    fn from(value: ForeignError) -> Self {
+        Self::Backend { msg: value.to_string() }
+    }
+}
+
+impl From<std::convert::Infallible> for PersistenceError {
+    fn from(value: std::convert::Infallible) -> Self {
+        match value {}
+    }
+}
+
+#[cfg(feature = "uniffi")]
+impl From<uniffi::UnexpectedUniFFICallbackError> for PersistenceError {
+    fn from(value: uniffi::UnexpectedUniFFICallbackError) -> Self {
+        Self::Backend { msg: value.reason }
+    }
+}
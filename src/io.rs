@@ -6,6 +6,7 @@ pub mod error {
     #[derive(Debug, PartialEq, Eq, thiserror::Error)]
     #[error("IO error: {message}")]
     #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+    #[cfg_attr(feature = "uniffi", uniffi::export(Display))]
     pub struct IoError {
         message: String,
     }
@@ -24,6 +25,7 @@ pub mod error {
 ///
 /// * `payjoin_directory`: The payjoin directory from which to fetch the ohttp keys.  This
 ///   directory stores and forwards payjoin client payloads.
+#[cfg_attr(feature = "uniffi", uniffi::export(async_runtime = "tokio"))]
 pub async fn fetch_ohttp_keys(
     ohttp_relay: &str,
     payjoin_directory: &str,
@@ -45,6 +47,7 @@ pub async fn fetch_ohttp_keys(
 ///
 /// * `cert_der`: The DER-encoded certificate to use for local HTTPS connections.
 #[cfg(feature = "_danger-local-https")]
+#[cfg_attr(feature = "uniffi", uniffi::export(async_runtime = "tokio"))]
 pub async fn fetch_ohttp_keys_with_cert(
     ohttp_relay: &str,
     payjoin_directory: &str,
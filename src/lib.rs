@@ -5,11 +5,17 @@ pub mod error;
 pub mod io;
 pub mod ohttp;
 pub mod output_substitution;
+pub mod persist;
 pub mod receive;
 pub mod request;
 pub mod send;
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test_fixtures;
 #[cfg(feature = "_test-utils")]
 pub mod test_utils;
+#[cfg(feature = "transport")]
+pub mod transport;
 pub mod uri;
 
 pub use payjoin::persist::NoopPersister;
@@ -17,13 +23,24 @@ pub use payjoin::persist::NoopPersister;
 pub use crate::bitcoin_ffi::*;
 pub use crate::ohttp::*;
 pub use crate::output_substitution::*;
+#[cfg(feature = "sqlite")]
+pub use crate::persist::SqlitePersister;
+pub use crate::persist::{
+    FilePersister, SessionEventRecord, SessionMetadata, SessionState, SessionStore,
+};
 #[cfg(feature = "uniffi")]
 pub use crate::receive::uni::*;
+#[cfg(feature = "uniffi")]
+pub use crate::receive::v1::uni::*;
 pub use crate::request::Request;
 #[cfg(feature = "uniffi")]
 pub use crate::send::uni::*;
 #[cfg(feature = "_test-utils")]
 pub use crate::test_utils::*;
-pub use crate::uri::{PjUri, Uri, Url};
+#[cfg(feature = "transport")]
+pub use crate::transport::{
+    PollConfig, SessionDriverError, Transport, TransportConfig, TransportError,
+};
+pub use crate::uri::{PjUri, PjUriBuilder, Uri, Url};
 #[cfg(feature = "uniffi")]
 uniffi::setup_scaffolding!();
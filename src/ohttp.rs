@@ -1,4 +1,4 @@
-pub use error::OhttpError;
+pub use error::{ClientResponseError, OhttpError};
 
 pub mod error {
     #[derive(Debug, PartialEq, Eq, thiserror::Error)]
@@ -17,6 +17,15 @@ pub mod error {
             OhttpError { message: value }
         }
     }
+
+    /// Error returned when a [`super::ClientResponse`] is used after it was consumed.
+    ///
+    /// Each context can decapsulate a single response. To retry, keep a copy made with
+    /// `to_json` before the first attempt.
+    #[derive(Debug, PartialEq, Eq, thiserror::Error)]
+    #[error("ClientResponse was already used to decapsulate a response")]
+    #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+    pub struct ClientResponseError;
 }
 
 impl From<payjoin::OhttpKeys> for OhttpKeys {
@@ -30,9 +39,67 @@ impl From<OhttpKeys> for payjoin::OhttpKeys {
     }
 }
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "uniffi", uniffi::export(Display, Eq))]
 #[derive(Debug, Clone)]
 pub struct OhttpKeys(payjoin::OhttpKeys);
 
+/// Keys are equal when their encoded KeyConfigs are, so a rotated key compares unequal.
+impl PartialEq for OhttpKeys {
+    fn eq(&self, other: &Self) -> bool {
+        self.encode().ok() == other.encode().ok()
+    }
+}
+
+impl Eq for OhttpKeys {}
+
+/// The bech32 encoding used in the `OH` parameter of a v2 `pj=` endpoint.
+impl std::fmt::Display for OhttpKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The contents of an OHTTP KeyConfig, as defined in RFC 9458.
+///
+/// Algorithms are identified by their IANA HPKE registry values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct OhttpKeyConfig {
+    pub key_id: u8,
+    pub kem_id: u16,
+    /// The encoded public key of the gateway.
+    pub public_key: Vec<u8>,
+    pub symmetric_suites: Vec<OhttpSymmetricSuite>,
+}
+
+/// A KDF and AEAD pair the OHTTP gateway accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct OhttpSymmetricSuite {
+    pub kdf_id: u16,
+    pub aead_id: u16,
+}
+
+impl OhttpKeyConfig {
+    fn decode(bytes: &[u8]) -> Result<Self, OhttpError> {
+        // Rejects malformed configs and KEMs or algorithms the ohttp crate does not support
+        ohttp::KeyConfig::decode(bytes)?;
+        let kem_id = u16::from_be_bytes([bytes[1], bytes[2]]);
+        let pk_len = ohttp::hpke::Kem::try_from(kem_id)?.n_pk();
+        let public_key = bytes[3..3 + pk_len].to_vec();
+        let symmetric_suites = bytes[3 + pk_len + 2..]
+            .chunks_exact(4)
+            .map(|suite| {
+                OhttpSymmetricSuite {
+                    kdf_id: u16::from_be_bytes([suite[0], suite[1]]),
+                    aead_id: u16::from_be_bytes([suite[2], suite[3]]),
+                }
+            })
+            .collect();
+        Ok(Self { key_id: bytes[0], kem_id, public_key, symmetric_suites })
+    }
+}
+
 #[cfg_attr(feature = "uniffi", uniffi::export)]
 impl OhttpKeys {
     /// Decode an OHTTP KeyConfig
@@ -48,18 +115,59 @@ impl OhttpKeys {
             .map_err(|e| OhttpError::from(e.to_string()))?;
         Ok(Self(res))
     }
+
+    /// Encode the OHTTP KeyConfig, the inverse of [`OhttpKeys::decode`]
+    pub fn encode(&self) -> Result<Vec<u8>, OhttpError> {
+        self.0.encode().map_err(|e| OhttpError::from(e.to_string()))
+    }
+
+    /// The key ID, KEM, public key and symmetric algorithms of the KeyConfig
+    pub fn key_config(&self) -> Result<OhttpKeyConfig, OhttpError> {
+        OhttpKeyConfig::decode(&self.encode()?)
+    }
+
+    /// The hex SHA-256 hash of the encoded KeyConfig, to tell keys apart in logs
+    pub fn fingerprint(&self) -> Result<String, OhttpError> {
+        Ok(sha256::Hash::hash(&self.encode()?).to_string())
+    }
 }
 
 use std::str::FromStr;
 use std::sync::Mutex;
 
+use payjoin::bitcoin::hashes::{sha256, Hash};
+
+use crate::error::{ContextSerializationError, SerdeJsonError};
+
+/// The OHTTP context needed to decapsulate the response to an encapsulated request.
+///
+/// It is consumed by the first response it decapsulates.
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
 pub struct ClientResponse(Mutex<Option<ohttp::ClientResponse>>);
 
-impl From<&ClientResponse> for ohttp::ClientResponse {
-    fn from(value: &ClientResponse) -> Self {
-        let mut data_guard = value.0.lock().unwrap();
-        Option::take(&mut *data_guard).expect("ClientResponse moved out of memory")
+impl ClientResponse {
+    /// Move the context out, failing if it was already used.
+    pub(crate) fn take(&self) -> Result<ohttp::ClientResponse, ClientResponseError> {
+        // A poisoned lock still guards a valid `Option`
+        let mut data_guard = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        data_guard.take().ok_or(ClientResponseError)
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl ClientResponse {
+    /// Serialize the context so a response can still be decapsulated after a restart.
+    ///
+    /// Fails if the context was already used.
+    pub fn to_json(&self) -> Result<String, ContextSerializationError> {
+        let data_guard = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let ctx = data_guard.as_ref().ok_or(ClientResponseError)?;
+        Ok(serde_json::to_string(ctx)?)
+    }
+
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+        serde_json::from_str::<ohttp::ClientResponse>(json).map_err(Into::into).map(Into::into)
     }
 }
 
@@ -68,3 +176,55 @@ impl From<ohttp::ClientResponse> for ClientResponse {
         Self(Mutex::new(Some(value)))
     }
 }
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+    use crate::test_fixtures::OHTTP_KEYS;
+
+    #[test]
+    fn key_config_is_inspectable() {
+        let keys = OhttpKeys::from_string(OHTTP_KEYS.to_string()).unwrap();
+        let config = keys.key_config().unwrap();
+        assert_eq!(config.key_id, 1);
+        assert_eq!(config.kem_id, 0x0016);
+        assert_eq!(config.public_key.len(), 65);
+        // HKDF-SHA256 with ChaCha20Poly1305
+        assert_eq!(
+            config.symmetric_suites,
+            vec![OhttpSymmetricSuite { kdf_id: 0x0001, aead_id: 0x0003 }]
+        );
+    }
+
+    #[test]
+    fn keys_round_trip_and_compare() {
+        let keys = OhttpKeys::from_string(OHTTP_KEYS.to_string()).unwrap();
+        assert_eq!(keys.to_string(), OHTTP_KEYS);
+        assert_eq!(OhttpKeys::decode(keys.encode().unwrap()).unwrap(), keys);
+
+        let mut rotated = keys.encode().unwrap();
+        rotated[0] = rotated[0].wrapping_add(1);
+        let rotated = OhttpKeys::decode(rotated).unwrap();
+        assert_ne!(rotated, keys);
+
+        let fingerprint = keys.fingerprint().unwrap();
+        assert_eq!(fingerprint.len(), 64);
+        assert_eq!(
+            OhttpKeys::decode(keys.encode().unwrap()).unwrap().fingerprint(),
+            Ok(fingerprint)
+        );
+        assert_ne!(rotated.fingerprint(), keys.fingerprint());
+    }
+
+    #[test]
+    fn key_configs_with_unknown_kems_are_rejected() {
+        let keys = OhttpKeys::from_string(OHTTP_KEYS.to_string()).unwrap();
+        let mut config = keys.encode().unwrap();
+        config[1..3].copy_from_slice(&0x7fffu16.to_be_bytes());
+        assert!(OhttpKeyConfig::decode(&config).is_err());
+
+        let known = keys.encode().unwrap();
+        assert!(OhttpKeyConfig::decode(&known[..known.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use fs2::FileExt;
+use payjoin::bitcoin::hashes::{sha256, Hash};
+use payjoin::persist::{Persister, Value};
+use payjoin::receive::v2::ReceiverToken;
+use payjoin::send::v2::SenderToken;
+
+use super::{unix_now, Session, SessionMetadata, SessionStore};
+use crate::error::PersistenceError;
+use crate::receive::ReceiverSession;
+use crate::send::SenderSession;
+
+const LOCK_FILE: &str = ".lock";
+
+/// On-disk layout of a session file.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoredSession<V> {
+    created_at: u64,
+    session: V,
+}
+
+/// Just the creation time of a [`StoredSession`], to preserve it across saves.
+#[derive(serde::Deserialize)]
+struct CreatedAt {
+    created_at: u64,
+}
+
+/// Persists each session as a JSON file in a directory.
+///
+/// Files are named after a hash of the session token, written atomically by renaming a
+/// temporary file into place, and guarded by an advisory lock on the directory so several
+/// processes can share it.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct FilePersister {
+    dir: PathBuf,
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl FilePersister {
+    /// Use `dir` as the session store, creating it if it does not exist.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn new(dir: String) -> Result<Self, PersistenceError> {
+        let dir = PathBuf::from(dir);
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+impl FilePersister {
+    fn path<V: Session>(&self, token: &impl Display) -> PathBuf {
+        let hash = sha256::Hash::hash(token.to_string().as_bytes());
+        self.dir.join(format!("{}-{hash}.json", V::KIND))
+    }
+
+    /// Take the directory lock, which is released when the returned file is dropped.
+    fn lock(&self, exclusive: bool) -> Result<File, PersistenceError> {
+        let file = OpenOptions::new().create(true).write(true).open(self.dir.join(LOCK_FILE))?;
+        if exclusive {
+            file.lock_exclusive()?;
+        } else {
+            file.lock_shared()?;
+        }
+        Ok(file)
+    }
+
+    fn store<V: Session>(&self, session: V, token: &impl Display) -> Result<(), PersistenceError> {
+        let path = self.path::<V>(token);
+        let _lock = self.lock(true)?;
+        let created_at = match fs::read(&path) {
+            // A corrupt file must not block saving the session over it
+            Ok(json) => {
+                serde_json::from_slice::<CreatedAt>(&json)
+                    .map_or_else(|_| unix_now(), |stored| stored.created_at)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => unix_now(),
+            Err(e) => return Err(e.into()),
+        };
+        let json = serde_json::to_vec(&StoredSession { created_at, session })
+            .map_err(|e| PersistenceError::Backend { msg: e.to_string() })?;
+        let tmp = path.with_extension("json.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&json)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        // The rename is only durable once the directory entry is synced too
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+        Ok(())
+    }
+
+    fn fetch<V: Session>(
+        &self,
+        token: &impl Display,
+    ) -> Result<StoredSession<V>, PersistenceError> {
+        let _lock = self.lock(false)?;
+        read(&self.path::<V>(token))?
+            .ok_or_else(|| PersistenceError::NotFound { token: token.to_string() })
+    }
+
+    fn fetch_all<V: Session>(&self) -> Result<Vec<V>, PersistenceError> {
+        let prefix = format!("{}-", V::KIND);
+        let _lock = self.lock(false)?;
+        let mut sessions = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let is_session = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".json"));
+            if !is_session {
+                continue;
+            }
+            match read::<V>(&path) {
+                Ok(Some(stored)) => sessions.push(stored.session),
+                // The file may have been removed since the directory was read
+                Ok(None) => {}
+                // A corrupt file must not hide the sessions that can still be resumed
+                Err(PersistenceError::Deserialization { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(sessions)
+    }
+
+    fn remove<V: Session>(&self, token: &impl Display) -> Result<(), PersistenceError> {
+        let _lock = self.lock(true)?;
+        match fs::remove_file(self.path::<V>(token)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn metadata_of<V: Session>(
+        &self,
+        token: &impl Display,
+    ) -> Result<SessionMetadata, PersistenceError> {
+        let stored = self.fetch::<V>(token)?;
+        Ok(SessionMetadata::new(token.to_string(), stored.created_at, stored.session.expires_at()))
+    }
+}
+
+/// Read a session file, returning `None` if it does not exist.
+fn read<V: Session>(path: &Path) -> Result<Option<StoredSession<V>>, PersistenceError> {
+    match fs::read(path) {
+        Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+macro_rules! impl_session_store {
+    ($session:ty, $token:ty) => {
+        impl Persister<$session> for FilePersister {
+            type Token = $token;
+            type Error = PersistenceError;
+
+            fn save(&mut self, session: $session) -> Result<Self::Token, Self::Error> {
+                let token = session.key();
+                self.store(session, &token)?;
+                Ok(token)
+            }
+
+            fn load(&self, token: Self::Token) -> Result<$session, Self::Error> {
+                Ok(self.fetch(&token)?.session)
+            }
+        }
+
+        impl SessionStore<$session> for FilePersister {
+            fn list(&self) -> Result<Vec<Self::Token>, Self::Error> {
+                Ok(self.fetch_all::<$session>()?.iter().map(Value::key).collect())
+            }
+
+            fn delete(&mut self, token: Self::Token) -> Result<(), Self::Error> {
+                self.remove::<$session>(&token)
+            }
+
+            fn metadata(&self, token: Self::Token) -> Result<SessionMetadata, Self::Error> {
+                self.metadata_of::<$session>(&token)
+            }
+        }
+    };
+}
+
+impl_session_store!(ReceiverSession, ReceiverToken);
+impl_session_store!(SenderSession, SenderToken);
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+    use crate::persist::SessionState;
+    use crate::test_fixtures::new_receiver;
+
+    fn dir_path(dir: &tempfile::TempDir) -> String {
+        dir.path().to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn receiver_round_trips_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persister = FilePersister::new(dir_path(&dir)).unwrap();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        let expected = token.to_string();
+
+        // A fresh persister over the same directory sees the saved session
+        let persister = FilePersister::new(dir_path(&dir)).unwrap();
+        let receiver = crate::receive::Receiver::load(token, &persister).unwrap();
+        assert_eq!(receiver.key().to_string(), expected);
+    }
+
+    #[test]
+    fn receiver_is_stored_as_the_start_of_its_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persister = FilePersister::new(dir_path(&dir)).unwrap();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+
+        let session = crate::receive::ReceiverSession::load(token.clone(), &persister).unwrap();
+        let names: Vec<String> = session.history().into_iter().map(|record| record.name).collect();
+        assert_eq!(names, ["created"]);
+        assert!(crate::receive::Receiver::load(token, &persister).is_ok());
+    }
+
+    #[test]
+    fn missing_file_is_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persister = FilePersister::new(dir_path(&dir)).unwrap();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        fs::remove_file(persister.path::<ReceiverSession>(&token)).unwrap();
+        assert!(matches!(
+            crate::receive::Receiver::load(token, &persister),
+            Err(PersistenceError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn sessions_are_listed_inspected_and_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persister = FilePersister::new(dir_path(&dir)).unwrap();
+        let active = new_receiver(None).persist(&mut persister).unwrap();
+        let expired = new_receiver(Some(0)).persist(&mut persister).unwrap();
+
+        let mut listed: Vec<String> = SessionStore::<ReceiverSession>::list(&persister)
+            .unwrap()
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        listed.sort();
+        let mut expected = vec![active.to_string(), expired.to_string()];
+        expected.sort();
+        assert_eq!(listed, expected);
+        assert!(SessionStore::<SenderSession>::list(&persister).unwrap().is_empty());
+
+        let metadata =
+            SessionStore::<ReceiverSession>::metadata(&persister, active.clone()).unwrap();
+        assert_eq!(metadata.token, active.to_string());
+        assert_eq!(metadata.state, SessionState::Active);
+        let metadata =
+            SessionStore::<ReceiverSession>::metadata(&persister, expired.clone()).unwrap();
+        assert_eq!(metadata.state, SessionState::Expired);
+
+        SessionStore::<ReceiverSession>::delete(&mut persister, expired.clone()).unwrap();
+        // Deleting twice is not an error
+        SessionStore::<ReceiverSession>::delete(&mut persister, expired).unwrap();
+        let listed = SessionStore::<ReceiverSession>::list(&persister).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].to_string(), active.to_string());
+    }
+
+    #[test]
+    fn corrupt_files_are_skipped_when_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persister = FilePersister::new(dir_path(&dir)).unwrap();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        fs::write(dir.path().join("receiver-corrupt.json"), b"not json").unwrap();
+
+        let listed = SessionStore::<ReceiverSession>::list(&persister).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].to_string(), token.to_string());
+    }
+
+    #[test]
+    fn corrupt_file_is_overwritten_on_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persister = FilePersister::new(dir_path(&dir)).unwrap();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        let path = persister.path::<ReceiverSession>(&token);
+        let receiver = crate::receive::Receiver::load(token, &persister).unwrap();
+        fs::write(path, b"not json").unwrap();
+
+        let token = ReceiverSession::new(receiver).persist(&mut persister).unwrap();
+        assert!(crate::receive::Receiver::load(token, &persister).is_ok());
+    }
+}
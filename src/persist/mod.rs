@@ -0,0 +1,170 @@
+//! Ready-made session persisters for the `Receiver` and `Sender` typestates.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use file::FilePersister;
+use payjoin::persist::{Persister, Value};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqlitePersister;
+
+pub mod file;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "uniffi")]
+pub mod uni;
+
+/// Where a stored session is in its lifecycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+pub enum SessionState {
+    /// The session can still be resumed.
+    Active,
+    /// The session is past its expiration time and can be deleted.
+    Expired,
+}
+
+/// Bookkeeping about a stored session, available without resuming it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct SessionMetadata {
+    /// The storage token, as displayed.
+    pub token: String,
+    /// When the session was first saved, in seconds since the Unix epoch.
+    pub created_at: u64,
+    /// When the session expires, in seconds since the Unix epoch.
+    pub expires_at: Option<u64>,
+    pub state: SessionState,
+}
+
+impl SessionMetadata {
+    /// Derive the [`SessionState`] from `expires_at` as of now.
+    pub fn new(token: String, created_at: u64, expires_at: Option<u64>) -> Self {
+        let state = match expires_at {
+            Some(expires_at) if expires_at <= unix_now() => SessionState::Expired,
+            _ => SessionState::Active,
+        };
+        Self { token, created_at, expires_at, state }
+    }
+}
+
+/// One entry of a session's history, in a form host languages can display or log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct SessionEventRecord {
+    /// When the event was recorded, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The event name, e.g. `proposal_received`.
+    pub name: String,
+    /// Further context such as an error message.
+    pub detail: Option<String>,
+}
+
+/// An event appended to a session's history along with when it happened.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct LoggedEvent<E> {
+    pub(crate) timestamp: u64,
+    pub(crate) event: E,
+}
+
+impl<E> LoggedEvent<E> {
+    pub(crate) fn now(event: E) -> Self {
+        Self { timestamp: unix_now(), event }
+    }
+}
+
+/// A [`Persister`] that can also enumerate, inspect and remove the sessions it holds.
+///
+/// This lets an application resume every in-flight session after a restart and clean up the
+/// ones that have finished or expired.
+pub trait SessionStore<V: Value>: Persister<V> {
+    /// Tokens of every stored session.
+    fn list(&self) -> Result<Vec<Self::Token>, Self::Error>;
+    /// Remove the session stored under `token`, if any.
+    fn delete(&mut self, token: Self::Token) -> Result<(), Self::Error>;
+    fn metadata(&self, token: Self::Token) -> Result<SessionMetadata, Self::Error>;
+}
+
+pub(crate) fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+pub(crate) fn unix_now() -> u64 {
+    unix_secs(SystemTime::now())
+}
+
+/// A session type the built-in persisters know how to store.
+pub(crate) trait Session: Value {
+    /// Prefix of session file names.
+    const KIND: &'static str;
+    /// SQLite table holding sessions of this type.
+    const TABLE: &'static str;
+
+    fn expires_at(&self) -> Option<u64>;
+}
+
+impl Session for crate::receive::ReceiverSession {
+    const KIND: &'static str = "receiver";
+    const TABLE: &'static str = "receiver_sessions";
+
+    fn expires_at(&self) -> Option<u64> {
+        self.receiver().expires_at()
+    }
+}
+
+impl Session for crate::send::SenderSession {
+    const KIND: &'static str = "sender";
+    const TABLE: &'static str = "sender_sessions";
+
+    fn expires_at(&self) -> Option<u64> {
+        self.sender().expires_at()
+    }
+}
+
+/// Implement [`Persister`] for the plain `Receiver` and `Sender` typestates on a persister that
+/// stores sessions. Each is saved as the start of its session and loaded back out of it.
+macro_rules! impl_typestate_persisters {
+    ($persister:ty) => {
+        impl Persister<payjoin::receive::v2::Receiver> for $persister {
+            type Token = payjoin::receive::v2::ReceiverToken;
+            type Error = crate::error::PersistenceError;
+
+            fn save(
+                &mut self,
+                receiver: payjoin::receive::v2::Receiver,
+            ) -> Result<Self::Token, Self::Error> {
+                let session = crate::receive::ReceiverSession::new(receiver.into());
+                Persister::<crate::receive::ReceiverSession>::save(self, session)
+            }
+
+            fn load(
+                &self,
+                token: Self::Token,
+            ) -> Result<payjoin::receive::v2::Receiver, Self::Error> {
+                let session = Persister::<crate::receive::ReceiverSession>::load(self, token)?;
+                Ok(session.receiver().into())
+            }
+        }
+
+        impl Persister<payjoin::send::v2::Sender> for $persister {
+            type Token = payjoin::send::v2::SenderToken;
+            type Error = crate::error::PersistenceError;
+
+            fn save(
+                &mut self,
+                sender: payjoin::send::v2::Sender,
+            ) -> Result<Self::Token, Self::Error> {
+                let session = crate::send::SenderSession::new(sender.into());
+                Persister::<crate::send::SenderSession>::save(self, session)
+            }
+
+            fn load(&self, token: Self::Token) -> Result<payjoin::send::v2::Sender, Self::Error> {
+                let session = Persister::<crate::send::SenderSession>::load(self, token)?;
+                Ok(session.sender().into())
+            }
+        }
+    };
+}
+
+impl_typestate_persisters!(FilePersister);
+#[cfg(feature = "sqlite")]
+impl_typestate_persisters!(SqlitePersister);
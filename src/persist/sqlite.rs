@@ -0,0 +1,265 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use payjoin::persist::{Persister, Value};
+use payjoin::receive::v2::ReceiverToken;
+use payjoin::send::v2::SenderToken;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{Session, SessionMetadata, SessionStore};
+use crate::error::PersistenceError;
+use crate::receive::ReceiverSession;
+use crate::send::SenderSession;
+
+/// Schema migrations, applied in order. The database's `user_version` records how many have run.
+const MIGRATIONS: &[&str] = &["CREATE TABLE receiver_sessions (
+        token TEXT NOT NULL PRIMARY KEY,
+        session TEXT NOT NULL,
+        created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+        updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+    ) WITHOUT ROWID;
+    CREATE TABLE sender_sessions (
+        token TEXT NOT NULL PRIMARY KEY,
+        session TEXT NOT NULL,
+        created_at INTEGER NOT NULL DEFAULT (unixepoch()),
+        updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+    ) WITHOUT ROWID;"];
+
+impl From<rusqlite::Error> for PersistenceError {
+    fn from(value: rusqlite::Error) -> Self {
+        Self::Backend { msg: value.to_string() }
+    }
+}
+
+/// Persists sessions as JSON rows in a SQLite database, keyed by session token.
+///
+/// Suited to servers handling many concurrent sessions. The connection is shared between
+/// clones, so one persister can serve every session in the process.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct SqlitePersister {
+    conn: Arc<Mutex<Connection>>,
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl SqlitePersister {
+    /// Open or create the database at `path` and bring its schema up to date.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn new(path: String) -> Result<Self, PersistenceError> {
+        let mut conn = Connection::open(path)?;
+        // Setting the journal mode returns the new mode as a row
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        migrate(&mut conn)?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), PersistenceError> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(PersistenceError::Backend {
+            msg: format!("Unknown database schema version {version}"),
+        });
+    }
+    let tx = conn.transaction()?;
+    for migration in &MIGRATIONS[version..] {
+        tx.execute_batch(migration)?;
+    }
+    tx.pragma_update(None, "user_version", MIGRATIONS.len())?;
+    tx.commit()?;
+    Ok(())
+}
+
+impl SqlitePersister {
+    fn conn(&self) -> Result<MutexGuard<'_, Connection>, PersistenceError> {
+        self.conn
+            .lock()
+            .map_err(|_| PersistenceError::Backend { msg: "Connection mutex poisoned".to_string() })
+    }
+
+    fn upsert<V: Session>(&self, session: &V, token: &str) -> Result<(), PersistenceError> {
+        let session = serde_json::to_string(session)
+            .map_err(|e| PersistenceError::Backend { msg: e.to_string() })?;
+        self.conn()?.execute(
+            &format!(
+                "INSERT INTO {} (token, session, created_at) VALUES (?1, ?2, unixepoch())
+                ON CONFLICT (token) DO UPDATE SET session = excluded.session,
+                updated_at = unixepoch()",
+                V::TABLE
+            ),
+            params![token, session],
+        )?;
+        Ok(())
+    }
+
+    /// The session stored under `token` and when it was created.
+    fn select<V: Session>(&self, token: &str) -> Result<(V, u64), PersistenceError> {
+        let (session, created_at): (String, u64) = self
+            .conn()?
+            .query_row(
+                &format!("SELECT session, created_at FROM {} WHERE token = ?1", V::TABLE),
+                params![token],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| PersistenceError::NotFound { token: token.to_string() })?;
+        Ok((serde_json::from_str(&session)?, created_at))
+    }
+
+    /// Every serialized session, oldest first.
+    ///
+    /// The upstream token types cannot be rebuilt from their text, so callers decode the
+    /// session to recover a typed token.
+    fn select_all<V: Session>(&self) -> Result<Vec<String>, PersistenceError> {
+        let conn = self.conn()?;
+        let mut stmt =
+            conn.prepare(&format!("SELECT session FROM {} ORDER BY created_at, token", V::TABLE))?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Tokens of every session that can still be decoded. Corrupt rows are skipped so they
+    /// do not hide the sessions that can be resumed.
+    fn tokens<V: Session>(&self) -> Result<Vec<V::Key>, PersistenceError> {
+        Ok(self
+            .select_all::<V>()?
+            .iter()
+            .filter_map(|session| serde_json::from_str::<V>(session).ok())
+            .map(|session| session.key())
+            .collect())
+    }
+
+    fn remove<V: Session>(&self, token: &str) -> Result<(), PersistenceError> {
+        self.conn()?
+            .execute(&format!("DELETE FROM {} WHERE token = ?1", V::TABLE), params![token])?;
+        Ok(())
+    }
+
+    fn metadata_of<V: Session>(&self, token: &str) -> Result<SessionMetadata, PersistenceError> {
+        let (session, created_at) = self.select::<V>(token)?;
+        Ok(SessionMetadata::new(token.to_string(), created_at, session.expires_at()))
+    }
+}
+
+macro_rules! impl_session_store {
+    ($session:ty, $token:ty) => {
+        impl Persister<$session> for SqlitePersister {
+            type Token = $token;
+            type Error = PersistenceError;
+
+            fn save(&mut self, session: $session) -> Result<Self::Token, Self::Error> {
+                let token = session.key();
+                self.upsert(&session, &token.to_string())?;
+                Ok(token)
+            }
+
+            fn load(&self, token: Self::Token) -> Result<$session, Self::Error> {
+                Ok(self.select(&token.to_string())?.0)
+            }
+        }
+
+        impl SessionStore<$session> for SqlitePersister {
+            fn list(&self) -> Result<Vec<Self::Token>, Self::Error> {
+                self.tokens::<$session>()
+            }
+
+            fn delete(&mut self, token: Self::Token) -> Result<(), Self::Error> {
+                self.remove::<$session>(&token.to_string())
+            }
+
+            fn metadata(&self, token: Self::Token) -> Result<SessionMetadata, Self::Error> {
+                self.metadata_of::<$session>(&token.to_string())
+            }
+        }
+    };
+}
+
+impl_session_store!(ReceiverSession, ReceiverToken);
+impl_session_store!(SenderSession, SenderToken);
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+    use crate::test_fixtures::new_receiver;
+
+    fn db_path(dir: &tempfile::TempDir) -> String {
+        dir.path().join("sessions.sqlite").to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn receiver_round_trips_through_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persister = SqlitePersister::new(db_path(&dir)).unwrap();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        let expected = token.to_string();
+        drop(persister);
+
+        // Reopening runs the migrations again without touching existing rows
+        let persister = SqlitePersister::new(db_path(&dir)).unwrap();
+        let receiver = crate::receive::Receiver::load(token, &persister).unwrap();
+        assert_eq!(receiver.key().to_string(), expected);
+    }
+
+    #[test]
+    fn missing_row_is_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let other_dir = tempfile::tempdir().unwrap();
+        let persister = SqlitePersister::new(db_path(&dir)).unwrap();
+        let mut other = SqlitePersister::new(db_path(&other_dir)).unwrap();
+        let token = new_receiver(None).persist(&mut other).unwrap();
+        assert!(matches!(
+            crate::receive::Receiver::load(token, &persister),
+            Err(PersistenceError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn sessions_are_listed_inspected_and_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persister = SqlitePersister::new(db_path(&dir)).unwrap();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+
+        let listed = SessionStore::<ReceiverSession>::list(&persister).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].to_string(), token.to_string());
+        let metadata =
+            SessionStore::<ReceiverSession>::metadata(&persister, token.clone()).unwrap();
+        assert_eq!(metadata.token, token.to_string());
+        assert_eq!(metadata.state, crate::persist::SessionState::Active);
+        assert!(metadata.created_at > 0);
+
+        SessionStore::<ReceiverSession>::delete(&mut persister, token).unwrap();
+        assert!(SessionStore::<ReceiverSession>::list(&persister).unwrap().is_empty());
+    }
+
+    #[test]
+    fn corrupt_rows_are_skipped_when_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persister = SqlitePersister::new(db_path(&dir)).unwrap();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        persister
+            .conn()
+            .unwrap()
+            .execute(
+                "INSERT INTO receiver_sessions (token, session) VALUES ('corrupt', 'not json')",
+                [],
+            )
+            .unwrap();
+
+        let listed = SessionStore::<ReceiverSession>::list(&persister).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].to_string(), token.to_string());
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = Connection::open(db_path(&dir)).unwrap();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1).unwrap();
+        drop(conn);
+        assert!(matches!(
+            SqlitePersister::new(db_path(&dir)),
+            Err(PersistenceError::Backend { .. })
+        ));
+    }
+}
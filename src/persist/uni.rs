@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use payjoin::persist::Persister;
+
+#[cfg(feature = "sqlite")]
+use super::SqlitePersister;
+use super::{FilePersister, SessionMetadata, SessionStore};
+use crate::error::PersistenceError;
+use crate::receive::uni::{ReceiverPersister, ReceiverSession, ReceiverToken};
+use crate::send::uni::{SenderPersister, SenderSession, SenderToken};
+
+/// Export a built-in persister through the foreign receiver and sender persister traits.
+macro_rules! impl_uni_persisters {
+    ($persister:ty) => {
+        #[uniffi::export]
+        impl $persister {
+            /// This persister as a [`ReceiverPersister`], for use with `NewReceiver::persist`,
+            /// `Receiver::load` and `ReceiverSession`.
+            pub fn receiver_persister(self: Arc<Self>) -> Arc<dyn ReceiverPersister> {
+                self
+            }
+
+            /// This persister as a [`SenderPersister`], for use with `NewSender::persist`,
+            /// `Sender::load` and `SenderSession`.
+            pub fn sender_persister(self: Arc<Self>) -> Arc<dyn SenderPersister> {
+                self
+            }
+        }
+
+        impl ReceiverPersister for $persister {
+            fn save(
+                &self,
+                session: Arc<ReceiverSession>,
+            ) -> Result<Arc<ReceiverToken>, PersistenceError> {
+                let session = crate::receive::ReceiverSession::from(session.as_ref());
+                let token =
+                    Persister::<crate::receive::ReceiverSession>::save(&mut self.clone(), session)?;
+                Ok(Arc::new(token.into()))
+            }
+
+            fn load(
+                &self,
+                token: Arc<ReceiverToken>,
+            ) -> Result<Arc<ReceiverSession>, PersistenceError> {
+                let session = Persister::<crate::receive::ReceiverSession>::load(
+                    self,
+                    (*token).clone().into(),
+                )?;
+                Ok(Arc::new(session.into()))
+            }
+
+            fn list(&self) -> Result<Vec<Arc<ReceiverToken>>, PersistenceError> {
+                let tokens = SessionStore::<crate::receive::ReceiverSession>::list(self)?;
+                Ok(tokens.into_iter().map(|token| Arc::new(token.into())).collect())
+            }
+
+            fn delete(&self, token: Arc<ReceiverToken>) -> Result<(), PersistenceError> {
+                SessionStore::<crate::receive::ReceiverSession>::delete(
+                    &mut self.clone(),
+                    (*token).clone().into(),
+                )
+            }
+
+            fn metadata(
+                &self,
+                token: Arc<ReceiverToken>,
+            ) -> Result<SessionMetadata, PersistenceError> {
+                SessionStore::<crate::receive::ReceiverSession>::metadata(
+                    self,
+                    (*token).clone().into(),
+                )
+            }
+        }
+
+        impl SenderPersister for $persister {
+            fn save(
+                &self,
+                session: Arc<SenderSession>,
+            ) -> Result<Arc<SenderToken>, PersistenceError> {
+                let session = crate::send::SenderSession::from(session.as_ref());
+                let token =
+                    Persister::<crate::send::SenderSession>::save(&mut self.clone(), session)?;
+                Ok(Arc::new(token.into()))
+            }
+
+            fn load(
+                &self,
+                token: Arc<SenderToken>,
+            ) -> Result<Arc<SenderSession>, PersistenceError> {
+                let session =
+                    Persister::<crate::send::SenderSession>::load(self, (*token).clone().into())?;
+                Ok(Arc::new(session.into()))
+            }
+
+            fn list(&self) -> Result<Vec<Arc<SenderToken>>, PersistenceError> {
+                let tokens = SessionStore::<crate::send::SenderSession>::list(self)?;
+                Ok(tokens.into_iter().map(|token| Arc::new(token.into())).collect())
+            }
+
+            fn delete(&self, token: Arc<SenderToken>) -> Result<(), PersistenceError> {
+                SessionStore::<crate::send::SenderSession>::delete(
+                    &mut self.clone(),
+                    (*token).clone().into(),
+                )
+            }
+
+            fn metadata(
+                &self,
+                token: Arc<SenderToken>,
+            ) -> Result<SessionMetadata, PersistenceError> {
+                SessionStore::<crate::send::SenderSession>::metadata(self, (*token).clone().into())
+            }
+        }
+    };
+}
+
+impl_uni_persisters!(FilePersister);
+#[cfg(feature = "sqlite")]
+impl_uni_persisters!(SqlitePersister);
@@ -0,0 +1,118 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use super::{Receiver, UncheckedProposal};
+use crate::transport::{Cancellation, PollConfig, SessionDriverError, Transport};
+
+/// Progress callbacks invoked by a [`ReceiverSessionDriver`].
+#[cfg_attr(feature = "uniffi", uniffi::export(with_foreign))]
+pub trait ReceiverSessionObserver: Send + Sync {
+    /// Called before each request to the directory, starting at `attempt` 1.
+    fn on_poll(&self, attempt: u32);
+    /// Called when a poll failed with a transient error and will be retried after `backoff_ms`.
+    fn on_retry(&self, error: String, backoff_ms: u64);
+    /// Called once the sender's Original PSBT has been received.
+    fn on_proposal_received(&self);
+}
+
+/// Polls the directory on behalf of a [`Receiver`] until the sender's proposal arrives.
+///
+/// The driver stops when the session expires, when [`ReceiverSessionDriver::cancel`] is
+/// called, or when a non-transient error occurs.
+pub struct ReceiverSessionDriver {
+    receiver: Receiver,
+    transport: Transport,
+    config: PollConfig,
+    observer: Option<Arc<dyn ReceiverSessionObserver>>,
+    cancellation: Cancellation,
+}
+
+impl ReceiverSessionDriver {
+    pub fn new(
+        receiver: Receiver,
+        transport: Transport,
+        config: PollConfig,
+        observer: Option<Arc<dyn ReceiverSessionObserver>>,
+    ) -> Self {
+        Self { receiver, transport, config, observer, cancellation: Cancellation::default() }
+    }
+
+    /// Poll until an [`UncheckedProposal`] is received.
+    pub async fn run(&self) -> Result<UncheckedProposal, SessionDriverError> {
+        let expiry = self.receiver.pj_uri().expiry();
+        let mut backoff = Duration::from_millis(self.config.initial_backoff_ms);
+        let max_backoff = Duration::from_millis(self.config.max_backoff_ms);
+        let mut attempt: u32 = 0;
+        loop {
+            let remaining = match expiry {
+                Some(expiry) => {
+                    expiry
+                        .duration_since(SystemTime::now())
+                        .ok()
+                        .filter(|remaining| !remaining.is_zero())
+                        .ok_or(SessionDriverError::Expired)?
+                }
+                None => Duration::MAX,
+            };
+            attempt = attempt.saturating_add(1);
+            if let Some(observer) = &self.observer {
+                observer.on_poll(attempt);
+            }
+            match self.cancellation.run(self.receiver.poll(&self.transport)).await? {
+                Ok(Some(proposal)) => {
+                    if let Some(observer) = &self.observer {
+                        observer.on_proposal_received();
+                    }
+                    return Ok(proposal);
+                }
+                Ok(None) => {}
+                Err(e) if e.is_transient() => {
+                    if let Some(observer) = &self.observer {
+                        observer.on_retry(e.to_string(), backoff.as_millis() as u64);
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+            self.cancellation.sleep(backoff.min(remaining)).await?;
+            backoff = backoff.saturating_mul(2).min(max_backoff);
+        }
+    }
+
+    /// Stop a running [`ReceiverSessionDriver::run`], which then returns
+    /// [`SessionDriverError::Cancelled`].
+    pub fn cancel(&self) {
+        self.cancellation.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{load_receiver, new_receiver};
+    use crate::{TransportConfig, Url};
+
+    fn driver(expire_after: Option<u64>) -> ReceiverSessionDriver {
+        let receiver = load_receiver(new_receiver(expire_after));
+        let relay = Url::parse("https://relay.example.com".to_string()).unwrap();
+        let transport = Transport::new(Arc::new(relay), TransportConfig::default()).unwrap();
+        ReceiverSessionDriver::new(receiver, transport, PollConfig::default(), None)
+    }
+
+    #[tokio::test]
+    async fn cancelled_driver_stops_polling() {
+        let driver = driver(None);
+        driver.cancel();
+        assert!(matches!(driver.run().await, Err(SessionDriverError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn expired_session_stops_polling() {
+        let driver = driver(Some(0));
+        assert!(matches!(driver.run().await, Err(SessionDriverError::Expired)));
+    }
+}
@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
+use payjoin::error_codes::ErrorCode;
 use payjoin::receive;
 
+use crate::ohttp::ClientResponseError;
+
 /// The top-level error type for the payjoin receiver
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -13,9 +16,12 @@ pub enum Error {
     /// V2-specific errors that are infeasable to reply to the sender
     #[error("Unreplyable error: {0}")]
     V2(Arc<SessionError>),
-    /// Catch-all for unhandled error variants
-    #[error("An unexpected error occurred")]
-    Unexpected,
+    /// The OHTTP context passed in was already used
+    #[error("{0}")]
+    ClientResponse(Arc<ClientResponseError>),
+    /// An error this version of the bindings does not recognize
+    #[error("An unexpected error occurred: {msg}")]
+    Unexpected { msg: String },
 }
 
 impl From<receive::Error> for Error {
@@ -23,11 +29,86 @@ impl From<receive::Error> for Error {
         match value {
             receive::Error::ReplyToSender(e) => Error::ReplyToSender(Arc::new(ReplyableError(e))),
             receive::Error::V2(e) => Error::V2(Arc::new(SessionError(e))),
-            _ => Error::Unexpected,
+            e => Error::unrecognized(e),
+        }
+    }
+}
+
+impl Error {
+    /// Keep the message of an upstream error this version of the bindings does not recognize.
+    fn unrecognized(error: impl std::fmt::Display) -> Self {
+        Error::Unexpected { msg: error.to_string() }
+    }
+}
+
+impl From<SessionError> for Error {
+    fn from(value: SessionError) -> Self {
+        Error::V2(Arc::new(value))
+    }
+}
+
+impl From<ClientResponseError> for Error {
+    fn from(value: ClientResponseError) -> Self {
+        Error::ClientResponse(Arc::new(value))
+    }
+}
+
+/// Error advancing a [`crate::receive::ReceiverSession`]
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
+pub enum ReceiverSessionError {
+    /// The step does not apply to the session's current typestate, or the session advanced
+    /// while the step was running
+    #[error("Cannot {step} in the {state} state")]
+    WrongState { step: String, state: String },
+    /// Errors that can be replied to the sender
+    #[error("Replyable error: {0}")]
+    ReplyToSender(Arc<ReplyableError>),
+    /// V2-specific errors that are infeasable to reply to the sender
+    #[error("Unreplyable error: {0}")]
+    V2(Arc<SessionError>),
+    /// The OHTTP context passed in was already used
+    #[error("{0}")]
+    ClientResponse(Arc<ClientResponseError>),
+    #[error("{0}")]
+    OutputSubstitution(Arc<OutputSubstitutionError>),
+    #[error("{0}")]
+    InputContribution(Arc<InputContributionError>),
+    /// An error this version of the bindings does not recognize
+    #[error("An unexpected error occurred: {msg}")]
+    Unexpected { msg: String },
+}
+
+impl From<Error> for ReceiverSessionError {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::ReplyToSender(e) => Self::ReplyToSender(e),
+            Error::V2(e) => Self::V2(e),
+            Error::ClientResponse(e) => Self::ClientResponse(e),
+            Error::Unexpected { msg } => Self::Unexpected { msg },
         }
     }
 }
 
+impl From<ReplyableError> for ReceiverSessionError {
+    fn from(value: ReplyableError) -> Self {
+        Self::ReplyToSender(Arc::new(value))
+    }
+}
+
+impl From<OutputSubstitutionError> for ReceiverSessionError {
+    fn from(value: OutputSubstitutionError) -> Self {
+        Self::OutputSubstitution(Arc::new(value))
+    }
+}
+
+impl From<InputContributionError> for ReceiverSessionError {
+    fn from(value: InputContributionError) -> Self {
+        Self::InputContribution(Arc::new(value))
+    }
+}
+
 /// The replyable error type for the payjoin receiver, representing failures need to be
 /// returned to the sender.
 ///
@@ -40,8 +121,29 @@ impl From<receive::Error> for Error {
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "uniffi", uniffi::export(Display))]
 pub struct ReplyableError(#[from] receive::ReplyableError);
 
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl ReplyableError {
+    /// The BIP 78 `errorCode` the sender will receive.
+    pub fn error_code(&self) -> String {
+        self.json_reply().error_code()
+    }
+
+    /// The message the sender will receive.
+    pub fn message(&self) -> String {
+        self.json_reply().message()
+    }
+}
+
+impl ReplyableError {
+    /// The reply the sender will receive, without consuming the error.
+    pub fn json_reply(&self) -> JsonReply {
+        JsonReply((&self.0).into())
+    }
+}
+
 /// The standard format for errors that can be replied as JSON.
 ///
 /// The JSON output includes the following fields:
@@ -67,6 +169,79 @@ impl From<ReplyableError> for JsonReply {
     }
 }
 
+/// Replies `unavailable` without revealing the implementation error to the sender.
+impl From<ImplementationError> for JsonReply {
+    fn from(value: ImplementationError) -> Self {
+        Self(receive::ReplyableError::Implementation(value.0).into())
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl JsonReply {
+    /// The receiver cannot process the request right now.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn unavailable(message: String) -> Self {
+        Self(receive::JsonReply::new(ErrorCode::Unavailable, message))
+    }
+
+    /// The receiver does not have enough funds to contribute.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn not_enough_money(message: String) -> Self {
+        Self(receive::JsonReply::new(ErrorCode::NotEnoughMoney, message))
+    }
+
+    /// The receiver does not support the sender's protocol version.
+    ///
+    /// `supported` lists the versions the receiver does support.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn version_unsupported(supported: Vec<u64>, message: String) -> Self {
+        Self(
+            receive::JsonReply::new(ErrorCode::VersionUnsupported, message)
+                .with_extra("supported", supported),
+        )
+    }
+
+    /// The receiver rejected the Original PSBT.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn original_psbt_rejected(message: String) -> Self {
+        Self(receive::JsonReply::new(ErrorCode::OriginalPsbtRejected, message))
+    }
+
+    /// Reply to a failure in the receiver's own implementation, without revealing its cause.
+    #[cfg(not(feature = "uniffi"))]
+    pub fn from_implementation_error(error: ImplementationError) -> Self {
+        error.into()
+    }
+
+    /// Reply to a failure in the receiver's own implementation, without revealing its cause.
+    #[cfg(feature = "uniffi")]
+    #[uniffi::constructor]
+    pub fn from_implementation_error(error: Arc<ImplementationError>) -> Self {
+        // The cause is never sent to the sender, so only its message needs to be kept
+        ImplementationError::from(error.to_string()).into()
+    }
+
+    /// The BIP 78 `errorCode`, e.g. `unavailable`.
+    pub fn error_code(&self) -> String {
+        self.field("errorCode")
+    }
+
+    pub fn message(&self) -> String {
+        self.field("message")
+    }
+
+    /// The reply body to send back.
+    pub fn to_json(&self) -> String {
+        self.0.to_json().to_string()
+    }
+}
+
+impl JsonReply {
+    fn field(&self, key: &str) -> String {
+        self.0.to_json()[key].as_str().unwrap_or_default().to_string()
+    }
+}
+
 /// Error arising due to the specific receiver implementation
 ///
 /// e.g. database errors, network failures, wallet errors
@@ -85,6 +260,7 @@ impl From<String> for ImplementationError {
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "uniffi", uniffi::export(Display))]
 pub struct SessionError(#[from] receive::v2::SessionError);
 
 /// Error that may occur when output substitution fails.
@@ -110,3 +286,54 @@ pub struct InputContributionError(#[from] receive::InputContributionError);
 #[error(transparent)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
 pub struct PsbtInputError(#[from] receive::PsbtInputError);
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn well_known_replies_carry_their_code() {
+        let reply = JsonReply::not_enough_money("Wallet is empty".to_string());
+        assert_eq!(reply.error_code(), "not-enough-money");
+        assert_eq!(reply.message(), "Wallet is empty");
+        assert_eq!(JsonReply::unavailable(String::new()).error_code(), "unavailable");
+        assert_eq!(
+            JsonReply::original_psbt_rejected(String::new()).error_code(),
+            "original-psbt-rejected"
+        );
+
+        let reply = JsonReply::version_unsupported(vec![1, 2], "Use v2".to_string());
+        let json: serde_json::Value = serde_json::from_str(&reply.to_json()).unwrap();
+        assert_eq!(json["errorCode"], "version-unsupported");
+        assert_eq!(json["supported"], serde_json::json!([1, 2]));
+    }
+
+    #[test]
+    fn receive_errors_keep_their_message() {
+        let cause = receive::ImplementationError::from("database is locked");
+        let error = Error::from(receive::Error::ReplyToSender(
+            receive::ReplyableError::Implementation(cause),
+        ));
+        let Error::ReplyToSender(e) = &error else {
+            panic!("expected a replyable error");
+        };
+        assert_eq!(e.error_code(), "unavailable");
+        assert!(error.to_string().contains("database is locked"));
+    }
+
+    #[test]
+    fn unrecognized_errors_keep_their_message() {
+        let error = Error::unrecognized("protocol violation");
+        assert!(matches!(&error, Error::Unexpected { msg } if msg == "protocol violation"));
+        assert!(error.to_string().contains("protocol violation"));
+    }
+
+    #[test]
+    fn implementation_errors_are_not_revealed() {
+        let error = ImplementationError::from("database is locked".to_string());
+        let reply = JsonReply::from_implementation_error(error);
+        assert_eq!(reply.error_code(), "unavailable");
+        assert!(!reply.to_json().contains("database"));
+    }
+}
@@ -3,22 +3,25 @@ use std::time::Duration;
 
 pub use error::{
     Error, ImplementationError, InputContributionError, JsonReply, OutputSubstitutionError,
-    PsbtInputError, ReplyableError, SelectionError, SessionError,
+    PsbtInputError, ReceiverSessionError, ReplyableError, SelectionError, SessionError,
 };
 use payjoin::bitcoin::psbt::Psbt;
-use payjoin::bitcoin::FeeRate;
 use payjoin::persist::{Persister, Value};
 use payjoin::receive::v2::ReceiverToken;
 
-use crate::bitcoin_ffi::{Address, OutPoint, Script, TxOut};
-pub use crate::error::SerdeJsonError;
+use crate::bitcoin_ffi::{Address, FeeRate, OutPoint, Script, TxOut};
+pub use crate::error::{ContextSerializationError, PersistenceError, SerdeJsonError};
 use crate::ohttp::OhttpKeys;
+use crate::persist::{LoggedEvent, SessionEventRecord};
 use crate::uri::error::IntoUrlError;
 use crate::{ClientResponse, OutputSubstitution, Request};
 
+#[cfg(feature = "transport")]
+pub mod driver;
 pub mod error;
 #[cfg(feature = "uniffi")]
 pub mod uni;
+pub mod v1;
 
 #[derive(Debug)]
 pub struct NewReceiver(payjoin::receive::v2::NewReceiver);
@@ -94,8 +97,11 @@ impl Receiver {
     pub fn load<P: Persister<payjoin::receive::v2::Receiver>>(
         token: P::Token,
         persister: &P,
-    ) -> Result<Self, ImplementationError> {
-        Ok(Receiver::from(persister.load(token).unwrap()))
+    ) -> Result<Self, PersistenceError>
+    where
+        PersistenceError: From<P::Error>,
+    {
+        persister.load(token).map(Into::into).map_err(Into::into)
     }
 
     pub fn extract_req(&self, ohttp_relay: String) -> Result<(Request, ClientResponse), Error> {
@@ -113,7 +119,7 @@ impl Receiver {
         ctx: &ClientResponse,
     ) -> Result<Option<UncheckedProposal>, Error> {
         <Self as Into<payjoin::receive::v2::Receiver>>::into(self.clone())
-            .process_res(body, ctx.into())
+            .process_res(body, ctx.take()?)
             .map(|e| e.map(|o| o.into()))
             .map_err(Into::into)
     }
@@ -123,6 +129,11 @@ impl Receiver {
         <Self as Into<payjoin::receive::v2::Receiver>>::into(self.clone()).pj_uri().into()
     }
 
+    /// Start building a Payjoin URI for this session, e.g. to request an amount
+    pub fn pj_uri_builder(&self) -> crate::PjUriBuilder {
+        self.pj_uri().into()
+    }
+
     ///The per-session public key to use as an identifier
     pub fn id(&self) -> String {
         <Self as Into<payjoin::receive::v2::Receiver>>::into(self.clone()).id().to_string()
@@ -141,9 +152,14 @@ impl Receiver {
     pub fn key(&self) -> ReceiverToken {
         self.0.key()
     }
+
+    /// When the session expires, in seconds since the Unix epoch.
+    pub fn expires_at(&self) -> Option<u64> {
+        self.pj_uri().expiry().map(crate::persist::unix_secs)
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct UncheckedProposal(payjoin::receive::v2::UncheckedProposal);
 
 impl From<payjoin::receive::v2::UncheckedProposal> for UncheckedProposal {
@@ -168,17 +184,14 @@ impl UncheckedProposal {
 
     pub fn check_broadcast_suitability(
         &self,
-        min_fee_rate: Option<u64>,
+        min_fee_rate: Option<FeeRate>,
         can_broadcast: impl Fn(&Vec<u8>) -> Result<bool, ImplementationError>,
     ) -> Result<MaybeInputsOwned, ReplyableError> {
         self.0
             .clone()
-            .check_broadcast_suitability(
-                min_fee_rate.map(FeeRate::from_sat_per_kwu),
-                |transaction| {
-                    Ok(can_broadcast(&payjoin::bitcoin::consensus::encode::serialize(transaction))?)
-                },
-            )
+            .check_broadcast_suitability(min_fee_rate.map(Into::into), |transaction| {
+                Ok(can_broadcast(&payjoin::bitcoin::consensus::encode::serialize(transaction))?)
+            })
             .map(Into::into)
             .map_err(Into::into)
     }
@@ -208,15 +221,12 @@ impl UncheckedProposal {
 
     /// Process an OHTTP Encapsulated HTTP POST Error response
     /// to ensure it has been posted properly
-    pub fn process_err_res(
-        &self,
-        body: &[u8],
-        context: &ClientResponse,
-    ) -> Result<(), SessionError> {
-        self.0.clone().process_err_res(body, context.into()).map_err(Into::into)
+    pub fn process_err_res(&self, body: &[u8], context: &ClientResponse) -> Result<(), Error> {
+        let context = context.take()?;
+        self.0.clone().process_err_res(body, context).map_err(|e| SessionError::from(e).into())
     }
 }
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct MaybeInputsOwned(payjoin::receive::v2::MaybeInputsOwned);
 
 impl From<payjoin::receive::v2::MaybeInputsOwned> for MaybeInputsOwned {
@@ -238,7 +248,7 @@ impl MaybeInputsOwned {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct MaybeInputsSeen(payjoin::receive::v2::MaybeInputsSeen);
 
 impl From<payjoin::receive::v2::MaybeInputsSeen> for MaybeInputsSeen {
@@ -264,7 +274,7 @@ impl MaybeInputsSeen {
 ///
 /// Only accept PSBTs that send us money.
 /// Identify those outputs with `identify_receiver_outputs()` to proceed
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct OutputsUnknown(payjoin::receive::v2::OutputsUnknown);
 
 impl From<payjoin::receive::v2::OutputsUnknown> for OutputsUnknown {
@@ -287,6 +297,7 @@ impl OutputsUnknown {
     }
 }
 
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct WantsOutputs(payjoin::receive::v2::WantsOutputs);
 
 impl From<payjoin::receive::v2::WantsOutputs> for WantsOutputs {
@@ -330,6 +341,7 @@ impl WantsOutputs {
     }
 }
 
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct WantsInputs(payjoin::receive::v2::WantsInputs);
 
 impl From<payjoin::receive::v2::WantsInputs> for WantsInputs {
@@ -402,6 +414,7 @@ impl From<payjoin::receive::InputPair> for InputPair {
     }
 }
 
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct ProvisionalProposal(pub payjoin::receive::v2::ProvisionalProposal);
 
 impl From<payjoin::receive::v2::ProvisionalProposal> for ProvisionalProposal {
@@ -414,8 +427,8 @@ impl ProvisionalProposal {
     pub fn finalize_proposal(
         &self,
         process_psbt: impl Fn(String) -> Result<String, ImplementationError>,
-        min_feerate_sat_per_vb: Option<u64>,
-        max_effective_fee_rate_sat_per_vb: Option<u64>,
+        min_fee_rate: Option<FeeRate>,
+        max_effective_fee_rate: Option<FeeRate>,
     ) -> Result<PayjoinProposal, ReplyableError> {
         self.0
             .clone()
@@ -424,15 +437,15 @@ impl ProvisionalProposal {
                     let psbt = process_psbt(pre_processed.to_string())?;
                     Ok(Psbt::from_str(&psbt)?)
                 },
-                min_feerate_sat_per_vb.and_then(FeeRate::from_sat_per_vb),
-                max_effective_fee_rate_sat_per_vb.and_then(FeeRate::from_sat_per_vb),
+                min_fee_rate.map(Into::into),
+                max_effective_fee_rate.map(Into::into),
             )
             .map(Into::into)
             .map_err(Into::into)
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct PayjoinProposal(pub payjoin::receive::v2::PayjoinProposal);
 
 impl From<PayjoinProposal> for payjoin::receive::v2::PayjoinProposal {
@@ -482,59 +495,663 @@ impl PayjoinProposal {
     /// After this function is called, the receiver can either wait for the Payjoin transaction to be broadcast or choose to broadcast the original PSBT.
     pub fn process_res(&self, body: &[u8], ohttp_context: &ClientResponse) -> Result<(), Error> {
         <PayjoinProposal as Into<payjoin::receive::v2::PayjoinProposal>>::into(self.clone())
-            .process_res(body, ohttp_context.into())
+            .process_res(body, ohttp_context.take()?)
             .map_err(|e| e.into())
     }
 }
 
-// #[cfg(test)]
-// #[cfg(not(feature = "uniffi"))]
-// mod test {
-//     use std::sync::Arc;
-
-//     use super::*;
-
-//     fn get_proposal_from_test_vector() -> Result<UncheckedProposal, Error> {
-//         // OriginalPSBT Test Vector from BIP
-//         // | InputScriptType | Orginal PSBT Fee rate | maxadditionalfeecontribution | additionalfeeoutputindex|
-//         // |-----------------|-----------------------|------------------------------|-------------------------|
-//         // | P2SH-P2WPKH     |  2 sat/vbyte          | 0.00000182                   | 0                       |
-//         let original_psbt =
-//             "cHNidP8BAHMCAAAAAY8nutGgJdyYGXWiBEb45Hoe9lWGbkxh/6bNiOJdCDuDAAAAAAD+////AtyVuAUAAAAAF6kUHehJ8GnSdBUOOv6ujXLrWmsJRDCHgIQeAAAAAAAXqRR3QJbbz0hnQ8IvQ0fptGn+votneofTAAAAAAEBIKgb1wUAAAAAF6kU3k4ekGHKWRNbA1rV5tR5kEVDVNCHAQcXFgAUx4pFclNVgo1WWAdN1SYNX8tphTABCGsCRzBEAiB8Q+A6dep+Rz92vhy26lT0AjZn4PRLi8Bf9qoB/CMk0wIgP/Rj2PWZ3gEjUkTlhDRNAQ0gXwTO7t9n+V14pZ6oljUBIQMVmsAaoNWHVMS02LfTSe0e388LNitPa1UQZyOihY+FFgABABYAFEb2Giu6c4KO5YW0pfw3lGp9jMUUAAA=";
-//         let body = original_psbt.as_bytes();
-
-//         let headers = Headers::from_vec(body.to_vec());
-//         UncheckedProposal::from_request(
-//             body.to_vec(),
-//             "?maxadditionalfeecontribution=182?additionalfeeoutputindex=0".to_string(),
-//             Arc::new(headers),
-//         )
-//     }
-
-//     #[test]
-//     fn can_get_proposal_from_request() {
-//         let proposal = get_proposal_from_test_vector();
-//         assert!(proposal.is_ok(), "OriginalPSBT should be a valid request");
-//     }
-
-//     #[test]
-//     fn unchecked_proposal_unlocks_after_checks() {
-//         let proposal = get_proposal_from_test_vector().unwrap();
-//         let _payjoin = proposal
-//             .assume_interactive_receiver()
-//             .clone()
-//             .check_inputs_not_owned(|_| Ok(true))
-//             .expect("No inputs should be owned")
-//             .check_no_inputs_seen_before(|_| Ok(false))
-//             .expect("No inputs should be seen before")
-//             .identify_receiver_outputs(|script| {
-//                 let network = payjoin::bitcoin::Network::Bitcoin;
-//                 let script = payjoin::bitcoin::ScriptBuf::from_bytes(script.to_vec());
-//                 Ok(payjoin::bitcoin::Address::from_script(&script, network).unwrap()
-//                     == payjoin::bitcoin::Address::from_str("3CZZi7aWFugaCdUCS15dgrUUViupmB8bVM")
-//                         .map(|x| x.require_network(network).unwrap())
-//                         .unwrap())
-//             })
-//             .expect("Receiver output should be identified");
-//     }
-// }
+/// Implement `to_json`/`from_json` for typestates so a session can be resumed from any step.
+macro_rules! impl_json {
+    ($($typestate:ident),*) => {
+        $(
+            impl $typestate {
+                pub fn to_json(&self) -> Result<String, SerdeJsonError> {
+                    serde_json::to_string(self).map_err(Into::into)
+                }
+
+                pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+                    serde_json::from_str(json).map_err(Into::into)
+                }
+            }
+        )*
+    };
+}
+
+impl_json!(
+    UncheckedProposal,
+    MaybeInputsOwned,
+    MaybeInputsSeen,
+    OutputsUnknown,
+    WantsOutputs,
+    WantsInputs,
+    ProvisionalProposal,
+    PayjoinProposal
+);
+
+/// Where a [`ReceiverSession`] is in the BIP 77 flow.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub enum ReceiverSessionState {
+    /// Waiting for the sender's Original PSBT.
+    Initialized,
+    UncheckedProposal(UncheckedProposal),
+    MaybeInputsOwned(MaybeInputsOwned),
+    MaybeInputsSeen(MaybeInputsSeen),
+    OutputsUnknown(OutputsUnknown),
+    WantsOutputs(WantsOutputs),
+    WantsInputs(WantsInputs),
+    ProvisionalProposal(ProvisionalProposal),
+    PayjoinProposal(PayjoinProposal),
+}
+
+impl ReceiverSessionState {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Initialized => "initialized",
+            Self::UncheckedProposal(_) => "unchecked_proposal",
+            Self::MaybeInputsOwned(_) => "maybe_inputs_owned",
+            Self::MaybeInputsSeen(_) => "maybe_inputs_seen",
+            Self::OutputsUnknown(_) => "outputs_unknown",
+            Self::WantsOutputs(_) => "wants_outputs",
+            Self::WantsInputs(_) => "wants_inputs",
+            Self::ProvisionalProposal(_) => "provisional_proposal",
+            Self::PayjoinProposal(_) => "payjoin_proposal",
+        }
+    }
+}
+
+/// A validation step the receiver runs on the sender's proposal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+pub enum ReceiverCheck {
+    BroadcastSuitability,
+    InputsNotOwned,
+    NoInputsSeenBefore,
+    ReceiverOutputs,
+    OutputSubstitution,
+    InputContribution,
+    Finalization,
+}
+
+/// Something that happened in a [`ReceiverSession`].
+///
+/// Events that advance the typestate carry the typestate they produced, so replaying the
+/// history reconstructs the current [`ReceiverSessionState`].
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub enum ReceiverSessionEvent {
+    Created,
+    /// The directory was polled for the sender's Original PSBT.
+    Polled,
+    ProposalReceived(UncheckedProposal),
+    BroadcastSuitabilityChecked(MaybeInputsOwned),
+    InputsNotOwnedChecked(MaybeInputsSeen),
+    NoInputsSeenBeforeChecked(OutputsUnknown),
+    ReceiverOutputsIdentified(WantsOutputs),
+    OutputsSubstituted(WantsOutputs),
+    OutputsCommitted(WantsInputs),
+    InputsContributed(WantsInputs),
+    InputsCommitted(ProvisionalProposal),
+    ProposalFinalized(PayjoinProposal),
+    /// The Payjoin Proposal was posted to the directory.
+    ProposalPosted,
+    CheckFailed {
+        check: ReceiverCheck,
+        error: String,
+    },
+    /// An error response was sent back to the sender.
+    ErrorReplied {
+        error: String,
+    },
+}
+
+impl ReceiverSessionEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Polled => "polled",
+            Self::ProposalReceived(_) => "proposal_received",
+            Self::BroadcastSuitabilityChecked(_) => "broadcast_suitability_checked",
+            Self::InputsNotOwnedChecked(_) => "inputs_not_owned_checked",
+            Self::NoInputsSeenBeforeChecked(_) => "no_inputs_seen_before_checked",
+            Self::ReceiverOutputsIdentified(_) => "receiver_outputs_identified",
+            Self::OutputsSubstituted(_) => "outputs_substituted",
+            Self::OutputsCommitted(_) => "outputs_committed",
+            Self::InputsContributed(_) => "inputs_contributed",
+            Self::InputsCommitted(_) => "inputs_committed",
+            Self::ProposalFinalized(_) => "proposal_finalized",
+            Self::ProposalPosted => "proposal_posted",
+            Self::CheckFailed { .. } => "check_failed",
+            Self::ErrorReplied { .. } => "error_replied",
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match self {
+            Self::CheckFailed { check, error } => Some(format!("{check:?}: {error}")),
+            Self::ErrorReplied { error } => Some(error.clone()),
+            _ => None,
+        }
+    }
+
+    /// Apply this event to `state`. Events that do not advance the typestate leave it as is.
+    fn apply(&self, state: ReceiverSessionState) -> ReceiverSessionState {
+        use ReceiverSessionState as State;
+        match self {
+            Self::ProposalReceived(p) => State::UncheckedProposal(p.clone()),
+            Self::BroadcastSuitabilityChecked(p) => State::MaybeInputsOwned(p.clone()),
+            Self::InputsNotOwnedChecked(p) => State::MaybeInputsSeen(p.clone()),
+            Self::NoInputsSeenBeforeChecked(p) => State::OutputsUnknown(p.clone()),
+            Self::ReceiverOutputsIdentified(p) | Self::OutputsSubstituted(p) => {
+                State::WantsOutputs(p.clone())
+            }
+            Self::OutputsCommitted(p) | Self::InputsContributed(p) => State::WantsInputs(p.clone()),
+            Self::InputsCommitted(p) => State::ProvisionalProposal(p.clone()),
+            Self::ProposalFinalized(p) => State::PayjoinProposal(p.clone()),
+            _ => state,
+        }
+    }
+}
+
+/// A [`Receiver`] together with the append-only history of its session.
+///
+/// Advance the session through its own methods, which record each step, and save it after
+/// every step so it can be resumed exactly where it left off. Persisters store a [`Receiver`]
+/// as the start of its session, under the same token.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ReceiverSession {
+    receiver: Receiver,
+    events: Vec<LoggedEvent<ReceiverSessionEvent>>,
+}
+
+impl From<ReceiverSession> for ReceiverToken {
+    fn from(value: ReceiverSession) -> Self {
+        value.key()
+    }
+}
+
+impl Value for ReceiverSession {
+    type Key = ReceiverToken;
+
+    fn key(&self) -> ReceiverToken {
+        self.receiver.key()
+    }
+}
+
+impl ReceiverSession {
+    /// Start a session that is waiting for the sender's Original PSBT.
+    pub fn new(receiver: Receiver) -> Self {
+        Self { receiver, events: vec![LoggedEvent::now(ReceiverSessionEvent::Created)] }
+    }
+
+    /// Loads a [`ReceiverSession`] from the provided persister using the storage token.
+    pub fn load<P: Persister<ReceiverSession>>(
+        token: P::Token,
+        persister: &P,
+    ) -> Result<Self, PersistenceError>
+    where
+        PersistenceError: From<P::Error>,
+    {
+        persister.load(token).map_err(Into::into)
+    }
+
+    /// Saves the session using the provided persister and returns the storage token.
+    pub fn persist<P: Persister<ReceiverSession>>(
+        &self,
+        persister: &mut P,
+    ) -> Result<P::Token, ImplementationError> {
+        persister.save(self.clone()).map_err(|e| ImplementationError::from(e.to_string()))
+    }
+
+    pub fn receiver(&self) -> Receiver {
+        self.receiver.clone()
+    }
+
+    fn record(&mut self, event: ReceiverSessionEvent) {
+        // Keep only the latest of consecutive polls, so waiting for the sender does not grow
+        // the history without bound
+        if matches!(event, ReceiverSessionEvent::Polled)
+            && matches!(self.events.last(), Some(logged) if matches!(logged.event, ReceiverSessionEvent::Polled))
+        {
+            self.events.pop();
+        }
+        self.events.push(LoggedEvent::now(event));
+    }
+
+    /// Record the typestate a step produced, or why `check` failed.
+    fn record_step<T: Clone, E: std::fmt::Display>(
+        &mut self,
+        check: ReceiverCheck,
+        result: Result<T, E>,
+        event: fn(T) -> ReceiverSessionEvent,
+    ) -> Result<T, E> {
+        match &result {
+            Ok(next) => self.record(event(next.clone())),
+            Err(e) => {
+                self.record(ReceiverSessionEvent::CheckFailed { check, error: e.to_string() })
+            }
+        }
+        result
+    }
+
+    fn wrong_state(&self, step: &str) -> ReceiverSessionError {
+        ReceiverSessionError::WrongState {
+            step: step.to_string(),
+            state: self.state().name().to_string(),
+        }
+    }
+
+    /// The current typestate, reconstructed by replaying the session history from the start.
+    pub fn state(&self) -> ReceiverSessionState {
+        self.events
+            .iter()
+            .fold(ReceiverSessionState::Initialized, |state, logged| logged.event.apply(state))
+    }
+
+    /// Process the directory's response to a poll, see [`Receiver::process_res`].
+    pub fn process_res(
+        &mut self,
+        body: &[u8],
+        ctx: &ClientResponse,
+    ) -> Result<Option<UncheckedProposal>, ReceiverSessionError> {
+        if !matches!(self.state(), ReceiverSessionState::Initialized) {
+            return Err(self.wrong_state("poll"));
+        }
+        let proposal = self.receiver.process_res(body, ctx)?;
+        self.record(ReceiverSessionEvent::Polled);
+        if let Some(proposal) = &proposal {
+            self.record(ReceiverSessionEvent::ProposalReceived(proposal.clone()));
+        }
+        Ok(proposal)
+    }
+
+    /// See [`UncheckedProposal::check_broadcast_suitability`].
+    pub fn check_broadcast_suitability(
+        &mut self,
+        min_fee_rate: Option<FeeRate>,
+        can_broadcast: impl Fn(&Vec<u8>) -> Result<bool, ImplementationError>,
+    ) -> Result<MaybeInputsOwned, ReceiverSessionError> {
+        let ReceiverSessionState::UncheckedProposal(proposal) = self.state() else {
+            return Err(self.wrong_state("check broadcast suitability"));
+        };
+        let result = proposal.check_broadcast_suitability(min_fee_rate, can_broadcast);
+        Ok(self.record_step(
+            ReceiverCheck::BroadcastSuitability,
+            result,
+            ReceiverSessionEvent::BroadcastSuitabilityChecked,
+        )?)
+    }
+
+    /// See [`UncheckedProposal::assume_interactive_receiver`].
+    pub fn assume_interactive_receiver(
+        &mut self,
+    ) -> Result<MaybeInputsOwned, ReceiverSessionError> {
+        let ReceiverSessionState::UncheckedProposal(proposal) = self.state() else {
+            return Err(self.wrong_state("assume an interactive receiver"));
+        };
+        let next = proposal.assume_interactive_receiver();
+        self.record(ReceiverSessionEvent::BroadcastSuitabilityChecked(next.clone()));
+        Ok(next)
+    }
+
+    /// See [`MaybeInputsOwned::check_inputs_not_owned`].
+    pub fn check_inputs_not_owned(
+        &mut self,
+        is_owned: impl Fn(&Vec<u8>) -> Result<bool, ImplementationError>,
+    ) -> Result<MaybeInputsSeen, ReceiverSessionError> {
+        let ReceiverSessionState::MaybeInputsOwned(proposal) = self.state() else {
+            return Err(self.wrong_state("check inputs not owned"));
+        };
+        let result = proposal.check_inputs_not_owned(is_owned);
+        Ok(self.record_step(
+            ReceiverCheck::InputsNotOwned,
+            result,
+            ReceiverSessionEvent::InputsNotOwnedChecked,
+        )?)
+    }
+
+    /// See [`MaybeInputsSeen::check_no_inputs_seen_before`].
+    pub fn check_no_inputs_seen_before(
+        &mut self,
+        is_known: impl Fn(&OutPoint) -> Result<bool, ImplementationError>,
+    ) -> Result<OutputsUnknown, ReceiverSessionError> {
+        let ReceiverSessionState::MaybeInputsSeen(proposal) = self.state() else {
+            return Err(self.wrong_state("check no inputs seen before"));
+        };
+        let result = proposal.check_no_inputs_seen_before(is_known);
+        Ok(self.record_step(
+            ReceiverCheck::NoInputsSeenBefore,
+            result,
+            ReceiverSessionEvent::NoInputsSeenBeforeChecked,
+        )?)
+    }
+
+    /// See [`OutputsUnknown::identify_receiver_outputs`].
+    pub fn identify_receiver_outputs(
+        &mut self,
+        is_receiver_output: impl Fn(&Vec<u8>) -> Result<bool, ImplementationError>,
+    ) -> Result<WantsOutputs, ReceiverSessionError> {
+        let ReceiverSessionState::OutputsUnknown(proposal) = self.state() else {
+            return Err(self.wrong_state("identify receiver outputs"));
+        };
+        let result = proposal.identify_receiver_outputs(is_receiver_output);
+        Ok(self.record_step(
+            ReceiverCheck::ReceiverOutputs,
+            result,
+            ReceiverSessionEvent::ReceiverOutputsIdentified,
+        )?)
+    }
+
+    /// See [`WantsOutputs::replace_receiver_outputs`].
+    pub fn replace_receiver_outputs(
+        &mut self,
+        replacement_outputs: Vec<TxOut>,
+        drain_script: &Script,
+    ) -> Result<WantsOutputs, ReceiverSessionError> {
+        let ReceiverSessionState::WantsOutputs(proposal) = self.state() else {
+            return Err(self.wrong_state("replace receiver outputs"));
+        };
+        let result = proposal.replace_receiver_outputs(replacement_outputs, drain_script);
+        Ok(self.record_step(
+            ReceiverCheck::OutputSubstitution,
+            result,
+            ReceiverSessionEvent::OutputsSubstituted,
+        )?)
+    }
+
+    /// See [`WantsOutputs::substitute_receiver_script`].
+    pub fn substitute_receiver_script(
+        &mut self,
+        output_script: &Script,
+    ) -> Result<WantsOutputs, ReceiverSessionError> {
+        let ReceiverSessionState::WantsOutputs(proposal) = self.state() else {
+            return Err(self.wrong_state("substitute the receiver script"));
+        };
+        let result = proposal.substitute_receiver_script(output_script);
+        Ok(self.record_step(
+            ReceiverCheck::OutputSubstitution,
+            result,
+            ReceiverSessionEvent::OutputsSubstituted,
+        )?)
+    }
+
+    /// See [`WantsOutputs::commit_outputs`].
+    pub fn commit_outputs(&mut self) -> Result<WantsInputs, ReceiverSessionError> {
+        let ReceiverSessionState::WantsOutputs(proposal) = self.state() else {
+            return Err(self.wrong_state("commit outputs"));
+        };
+        let next = proposal.commit_outputs();
+        self.record(ReceiverSessionEvent::OutputsCommitted(next.clone()));
+        Ok(next)
+    }
+
+    /// See [`WantsInputs::contribute_inputs`].
+    pub fn contribute_inputs(
+        &mut self,
+        replacement_inputs: Vec<InputPair>,
+    ) -> Result<WantsInputs, ReceiverSessionError> {
+        let ReceiverSessionState::WantsInputs(proposal) = self.state() else {
+            return Err(self.wrong_state("contribute inputs"));
+        };
+        let result = proposal.contribute_inputs(replacement_inputs);
+        Ok(self.record_step(
+            ReceiverCheck::InputContribution,
+            result,
+            ReceiverSessionEvent::InputsContributed,
+        )?)
+    }
+
+    /// See [`WantsInputs::commit_inputs`].
+    pub fn commit_inputs(&mut self) -> Result<ProvisionalProposal, ReceiverSessionError> {
+        let ReceiverSessionState::WantsInputs(proposal) = self.state() else {
+            return Err(self.wrong_state("commit inputs"));
+        };
+        let next = proposal.commit_inputs();
+        self.record(ReceiverSessionEvent::InputsCommitted(next.clone()));
+        Ok(next)
+    }
+
+    /// See [`ProvisionalProposal::finalize_proposal`].
+    pub fn finalize_proposal(
+        &mut self,
+        process_psbt: impl Fn(String) -> Result<String, ImplementationError>,
+        min_fee_rate: Option<FeeRate>,
+        max_effective_fee_rate: Option<FeeRate>,
+    ) -> Result<PayjoinProposal, ReceiverSessionError> {
+        let ReceiverSessionState::ProvisionalProposal(proposal) = self.state() else {
+            return Err(self.wrong_state("finalize the proposal"));
+        };
+        let result = proposal.finalize_proposal(process_psbt, min_fee_rate, max_effective_fee_rate);
+        Ok(self.record_step(
+            ReceiverCheck::Finalization,
+            result,
+            ReceiverSessionEvent::ProposalFinalized,
+        )?)
+    }
+
+    /// Process the directory's response to posting the Payjoin Proposal, see
+    /// [`PayjoinProposal::process_res`].
+    pub fn process_proposal_res(
+        &mut self,
+        body: &[u8],
+        ctx: &ClientResponse,
+    ) -> Result<(), ReceiverSessionError> {
+        let ReceiverSessionState::PayjoinProposal(proposal) = self.state() else {
+            return Err(self.wrong_state("post the proposal"));
+        };
+        proposal.process_res(body, ctx)?;
+        self.record(ReceiverSessionEvent::ProposalPosted);
+        Ok(())
+    }
+
+    /// Process the directory's response to posting `reply` to the sender, see
+    /// [`UncheckedProposal::process_err_res`].
+    ///
+    /// The reply may follow a failure at any step after the sender's proposal was received.
+    pub fn process_err_res(
+        &mut self,
+        reply: &JsonReply,
+        body: &[u8],
+        ctx: &ClientResponse,
+    ) -> Result<(), ReceiverSessionError> {
+        let proposal = self.events.iter().rev().find_map(|logged| {
+            match &logged.event {
+                ReceiverSessionEvent::ProposalReceived(proposal) => Some(proposal.clone()),
+                _ => None,
+            }
+        });
+        let Some(proposal) = proposal else {
+            return Err(self.wrong_state("reply with an error"));
+        };
+        proposal.process_err_res(body, ctx)?;
+        self.record(ReceiverSessionEvent::ErrorReplied { error: reply.error_code() });
+        Ok(())
+    }
+
+    pub fn events(&self) -> Vec<ReceiverSessionEvent> {
+        self.events.iter().map(|logged| logged.event.clone()).collect()
+    }
+
+    /// The session history with timestamps, for display or logging.
+    pub fn history(&self) -> Vec<SessionEventRecord> {
+        self.events
+            .iter()
+            .map(|logged| {
+                SessionEventRecord {
+                    timestamp: logged.timestamp,
+                    name: logged.event.name().to_string(),
+                    detail: logged.event.detail(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, SerdeJsonError> {
+        serde_json::to_string(self).map_err(Into::into)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+        serde_json::from_str(json).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{new_receiver, receiver, InMemoryPersister};
+
+    #[test]
+    fn load_persisted_receiver() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        assert!(Receiver::load(token, &persister).is_ok());
+    }
+
+    #[test]
+    fn load_missing_receiver_is_not_found() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        persister.values.clear();
+        assert!(matches!(
+            Receiver::load(token, &persister),
+            Err(PersistenceError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn load_corrupted_receiver_is_deserialization_error() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        persister.values.insert(token.to_string(), "{\"corrupted\":true}".to_string());
+        assert!(matches!(
+            Receiver::load(token, &persister),
+            Err(PersistenceError::Deserialization { .. })
+        ));
+    }
+
+    #[test]
+    fn load_from_failing_backend_is_backend_error() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        persister.unavailable = true;
+        assert!(matches!(Receiver::load(token, &persister), Err(PersistenceError::Backend { .. })));
+    }
+
+    #[test]
+    fn receiver_session_round_trips_with_its_state() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        let mut session = ReceiverSession::new(Receiver::load(token, &persister).unwrap());
+        session.record(ReceiverSessionEvent::Polled);
+        assert!(matches!(session.state(), ReceiverSessionState::Initialized));
+
+        let json = session.to_json().unwrap();
+        let resumed = ReceiverSession::from_json(&json).unwrap();
+        assert_eq!(resumed.key().to_string(), session.key().to_string());
+        assert!(matches!(resumed.state(), ReceiverSessionState::Initialized));
+    }
+
+    #[test]
+    fn failed_poll_is_not_recorded() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        let mut session = ReceiverSession::new(Receiver::load(token, &persister).unwrap());
+        let (_, ctx) =
+            session.receiver().extract_req("https://relay.example.com".to_string()).unwrap();
+
+        assert!(session.process_res(b"not an ohttp response", &ctx).is_err());
+        let names: Vec<String> = session.history().into_iter().map(|record| record.name).collect();
+        assert_eq!(names, ["created"]);
+    }
+
+    #[test]
+    fn undecapsulated_responses_are_session_errors() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        let receiver = Receiver::load(token, &persister).unwrap();
+        let (_, ctx) = receiver.extract_req("https://relay.example.com".to_string()).unwrap();
+
+        let error = receiver.process_res(b"not an ohttp response", &ctx).unwrap_err();
+        let Error::V2(e) = &error else {
+            panic!("expected a session error");
+        };
+        assert!(error.to_string().contains(&e.to_string()));
+        assert!(matches!(
+            receiver.process_res(b"not an ohttp response", &ctx),
+            Err(Error::ClientResponse(_))
+        ));
+    }
+
+    #[test]
+    fn receiver_session_history_is_append_only() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        let mut session = ReceiverSession::new(Receiver::load(token, &persister).unwrap());
+        session.record(ReceiverSessionEvent::Polled);
+        let failed: Result<MaybeInputsOwned, _> = Err("fee too low");
+        assert!(session
+            .record_step(
+                ReceiverCheck::BroadcastSuitability,
+                failed,
+                ReceiverSessionEvent::BroadcastSuitabilityChecked,
+            )
+            .is_err());
+        session.record(ReceiverSessionEvent::ErrorReplied { error: "unavailable".to_string() });
+
+        let history = session.history();
+        let names: Vec<&str> = history.iter().map(|record| record.name.as_str()).collect();
+        assert_eq!(names, ["created", "polled", "check_failed", "error_replied"]);
+        assert_eq!(history[2].detail.as_deref(), Some("BroadcastSuitability: fee too low"));
+        assert!(history.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        // Failures do not move the typestate
+        assert!(matches!(session.state(), ReceiverSessionState::Initialized));
+    }
+
+    #[test]
+    fn consecutive_polls_are_recorded_once() {
+        let mut session = ReceiverSession::new(receiver());
+        session.record(ReceiverSessionEvent::Polled);
+        session.record(ReceiverSessionEvent::Polled);
+        let names: Vec<String> = session.history().into_iter().map(|record| record.name).collect();
+        assert_eq!(names, ["created", "polled"]);
+    }
+
+    #[test]
+    fn steps_are_rejected_in_other_states() {
+        let mut session = ReceiverSession::new(receiver());
+        let error = session.assume_interactive_receiver().unwrap_err();
+        assert!(matches!(
+            &error,
+            ReceiverSessionError::WrongState { state, .. } if state == "initialized"
+        ));
+        assert!(matches!(session.commit_outputs(), Err(ReceiverSessionError::WrongState { .. })));
+        assert!(matches!(
+            session.finalize_proposal(Ok, None, None),
+            Err(ReceiverSessionError::WrongState { .. })
+        ));
+        let names: Vec<String> = session.history().into_iter().map(|record| record.name).collect();
+        assert_eq!(names, ["created"]);
+    }
+
+    #[test]
+    fn client_response_is_consumed_once_but_can_be_restored() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_receiver(None).persist(&mut persister).unwrap();
+        let receiver = Receiver::load(token, &persister).unwrap();
+        let (_, ctx) = receiver.extract_req("https://relay.example.com".to_string()).unwrap();
+        let json = ctx.to_json().unwrap();
+
+        assert!(receiver.process_res(b"not an ohttp response", &ctx).is_err());
+        assert!(matches!(
+            receiver.process_res(b"not an ohttp response", &ctx),
+            Err(Error::ClientResponse(_))
+        ));
+        assert!(matches!(ctx.to_json(), Err(ContextSerializationError::ClientResponseUsed(_))));
+
+        // A copy saved before the first attempt can be used to retry
+        let restored = ClientResponse::from_json(&json).unwrap();
+        assert!(!matches!(
+            receiver.process_res(b"not an ohttp response", &restored),
+            Err(Error::ClientResponse(_))
+        ));
+    }
+}
@@ -1,11 +1,15 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
-use super::InputPair;
-use crate::bitcoin_ffi::{Address, OutPoint, Script, TxOut};
+use payjoin::persist::Value;
+
+use super::{InputPair, ReceiverCheck};
+use crate::bitcoin_ffi::{Address, FeeRate, OutPoint, Script, TxOut};
 use crate::error::ForeignError;
+use crate::persist::{SessionEventRecord, SessionMetadata};
 pub use crate::receive::{
     Error, ImplementationError, InputContributionError, JsonReply, OutputSubstitutionError,
-    ReplyableError, SelectionError, SerdeJsonError, SessionError,
+    PersistenceError, ReceiverSessionError, ReplyableError, SelectionError, SerdeJsonError,
+    SessionError,
 };
 use crate::uri::error::IntoUrlError;
 use crate::{ClientResponse, OhttpKeys, OutputSubstitution, Request};
@@ -111,11 +115,8 @@ impl Receiver {
     pub fn load(
         token: Arc<ReceiverToken>,
         persister: Arc<dyn ReceiverPersister>,
-    ) -> Result<Self, ImplementationError> {
-        Ok(super::Receiver::from(
-            (*persister.load(token).map_err(|e| ImplementationError::from(e.to_string()))?).clone(),
-        )
-        .into())
+    ) -> Result<Self, PersistenceError> {
+        Ok(persister.load(token)?.receiver())
     }
 
     /// The contents of the `&pj=` query parameter including the base64url-encoded public key receiver subdirectory.
@@ -124,6 +125,11 @@ impl Receiver {
         self.0.pj_uri()
     }
 
+    /// Start building a Payjoin URI for this session, e.g. to request an amount.
+    pub fn pj_uri_builder(&self) -> crate::PjUriBuilder {
+        self.0.pj_uri_builder()
+    }
+
     pub fn extract_req(&self, ohttp_relay: String) -> Result<RequestResponse, Error> {
         self.0
             .extract_req(ohttp_relay)
@@ -158,6 +164,65 @@ impl Receiver {
     pub fn key(&self) -> ReceiverToken {
         self.0.key().into()
     }
+
+    /// When the session expires, in seconds since the Unix epoch.
+    pub fn expires_at(&self) -> Option<u64> {
+        self.0.expires_at()
+    }
+}
+
+#[cfg(feature = "transport")]
+#[uniffi::export(async_runtime = "tokio")]
+impl Receiver {
+    /// Poll the directory once for a proposal from the sender.
+    ///
+    /// Returns `None` if the sender has not posted an Original PSBT yet.
+    pub async fn poll(
+        &self,
+        transport: Arc<crate::transport::Transport>,
+    ) -> Result<Option<Arc<UncheckedProposal>>, crate::transport::TransportError> {
+        self.0.poll(&transport).await.map(|e| e.map(|x| Arc::new(x.into())))
+    }
+}
+
+/// Polls the directory on behalf of a [`Receiver`] until the sender's proposal arrives.
+#[cfg(feature = "transport")]
+#[derive(uniffi::Object)]
+pub struct ReceiverSessionDriver(super::driver::ReceiverSessionDriver);
+
+#[cfg(feature = "transport")]
+#[uniffi::export(async_runtime = "tokio")]
+impl ReceiverSessionDriver {
+    #[uniffi::constructor]
+    pub fn new(
+        receiver: Arc<Receiver>,
+        transport: Arc<crate::transport::Transport>,
+        config: crate::transport::PollConfig,
+        observer: Option<Arc<dyn super::driver::ReceiverSessionObserver>>,
+    ) -> Self {
+        Self(super::driver::ReceiverSessionDriver::new(
+            receiver.0.clone(),
+            (*transport).clone(),
+            config,
+            observer,
+        ))
+    }
+
+    /// Poll until an [`UncheckedProposal`] is received, the session expires or the driver is
+    /// cancelled.
+    pub async fn run(
+        &self,
+    ) -> Result<Arc<UncheckedProposal>, crate::transport::SessionDriverError> {
+        self.0.run().await.map(|p| Arc::new(p.into()))
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
 }
 
 #[derive(uniffi::Record)]
@@ -201,12 +266,12 @@ impl UncheckedProposal {
     /// Call this after checking downstream.
     pub fn check_broadcast_suitability(
         &self,
-        min_fee_rate: Option<u64>,
+        min_fee_rate: Option<Arc<FeeRate>>,
         can_broadcast: Arc<dyn CanBroadcast>,
     ) -> Result<Arc<MaybeInputsOwned>, ReplyableError> {
         self.0
             .clone()
-            .check_broadcast_suitability(min_fee_rate, |transaction| {
+            .check_broadcast_suitability(min_fee_rate.map(|r| (*r).clone()), |transaction| {
                 can_broadcast
                     .callback(transaction.to_vec())
                     .map_err(|e| ImplementationError::from(e.to_string()))
@@ -237,11 +302,7 @@ impl UncheckedProposal {
 
     /// Process an OHTTP Encapsulated HTTP POST Error response
     /// to ensure it has been posted properly
-    pub fn process_err_res(
-        &self,
-        body: &[u8],
-        context: Arc<ClientResponse>,
-    ) -> Result<(), SessionError> {
+    pub fn process_err_res(&self, body: &[u8], context: Arc<ClientResponse>) -> Result<(), Error> {
         self.0.clone().process_err_res(body, &context)
     }
 }
@@ -446,8 +507,8 @@ impl ProvisionalProposal {
     pub fn finalize_proposal(
         &self,
         process_psbt: Arc<dyn ProcessPsbt>,
-        min_feerate_sat_per_vb: Option<u64>,
-        max_effective_fee_rate_sat_per_vb: Option<u64>,
+        min_fee_rate: Option<Arc<FeeRate>>,
+        max_effective_fee_rate: Option<Arc<FeeRate>>,
     ) -> Result<Arc<PayjoinProposal>, ReplyableError> {
         self.0
             .finalize_proposal(
@@ -456,8 +517,8 @@ impl ProvisionalProposal {
                         .callback(psbt.to_string())
                         .map_err(|e| ImplementationError::from(e.to_string()))
                 },
-                min_feerate_sat_per_vb,
-                max_effective_fee_rate_sat_per_vb,
+                min_fee_rate.map(|r| (*r).clone()),
+                max_effective_fee_rate.map(|r| (*r).clone()),
             )
             .map(|e| Arc::new(e.into()))
     }
@@ -515,13 +576,33 @@ impl PayjoinProposal {
     }
 }
 
+#[cfg(feature = "transport")]
+#[uniffi::export(async_runtime = "tokio")]
+impl PayjoinProposal {
+    /// Post the Payjoin Proposal PSBT to the directory for the sender to pick up.
+    pub async fn send(
+        &self,
+        transport: Arc<crate::transport::Transport>,
+    ) -> Result<(), crate::transport::TransportError> {
+        self.0.send(&transport).await
+    }
+}
+
 #[uniffi::export(with_foreign)]
 pub trait ReceiverPersister: Send + Sync {
-    fn save(&self, receiver: Arc<Receiver>) -> Result<Arc<ReceiverToken>, ForeignError>;
-    fn load(&self, token: Arc<ReceiverToken>) -> Result<Arc<Receiver>, ForeignError>;
+    /// Store `session`, replacing any session saved under the same token.
+    fn save(&self, session: Arc<ReceiverSession>) -> Result<Arc<ReceiverToken>, PersistenceError>;
+    fn load(&self, token: Arc<ReceiverToken>) -> Result<Arc<ReceiverSession>, PersistenceError>;
+    /// Tokens of every stored receiver session.
+    fn list(&self) -> Result<Vec<Arc<ReceiverToken>>, PersistenceError>;
+    /// Remove the receiver session stored under `token`, if any.
+    fn delete(&self, token: Arc<ReceiverToken>) -> Result<(), PersistenceError>;
+    fn metadata(&self, token: Arc<ReceiverToken>) -> Result<SessionMetadata, PersistenceError>;
 }
 
 /// Adapter for the ReceiverPersister trait to use the save and load callbacks.
+///
+/// A plain [`Receiver`] is stored as the start of its session.
 struct CallbackPersisterAdapter {
     callback_persister: Arc<dyn ReceiverPersister>,
 }
@@ -532,20 +613,472 @@ impl CallbackPersisterAdapter {
     }
 }
 
+impl payjoin::persist::Persister<super::ReceiverSession> for CallbackPersisterAdapter {
+    type Token = ReceiverToken;
+    type Error = PersistenceError;
+
+    fn save(&mut self, session: super::ReceiverSession) -> Result<Self::Token, Self::Error> {
+        let res = self.callback_persister.save(Arc::new(session.into()))?;
+        Ok((*res).clone())
+    }
+
+    fn load(&self, token: Self::Token) -> Result<super::ReceiverSession, Self::Error> {
+        self.callback_persister.load(token.into()).map(|session| session.as_ref().into())
+    }
+}
+
 impl payjoin::persist::Persister<payjoin::receive::v2::Receiver> for CallbackPersisterAdapter {
     type Token = ReceiverToken;
-    type Error = ForeignError;
+    type Error = PersistenceError;
 
     fn save(
         &mut self,
         receiver: payjoin::receive::v2::Receiver,
     ) -> Result<Self::Token, Self::Error> {
-        let receiver = Receiver(super::Receiver::from(receiver));
-        let res = self.callback_persister.save(receiver.into())?;
-        Ok((*res).clone())
+        let session = super::ReceiverSession::new(receiver.into());
+        payjoin::persist::Persister::<super::ReceiverSession>::save(self, session)
     }
 
     fn load(&self, token: Self::Token) -> Result<payjoin::receive::v2::Receiver, Self::Error> {
-        self.callback_persister.load(token.into()).map(|receiver| (*receiver).clone().0 .0)
+        let session = payjoin::persist::Persister::<super::ReceiverSession>::load(self, token)?;
+        Ok(session.receiver().into())
+    }
+}
+
+/// Export `to_json`/`from_json` for typestates so a session can be resumed from any step.
+macro_rules! impl_json {
+    ($($typestate:ident),*) => {
+        $(
+            #[uniffi::export]
+            impl $typestate {
+                pub fn to_json(&self) -> Result<String, SerdeJsonError> {
+                    self.0.to_json()
+                }
+
+                #[uniffi::constructor]
+                pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+                    super::$typestate::from_json(json).map(Into::into)
+                }
+            }
+        )*
+    };
+}
+
+impl_json!(
+    UncheckedProposal,
+    MaybeInputsOwned,
+    MaybeInputsSeen,
+    OutputsUnknown,
+    WantsOutputs,
+    WantsInputs,
+    ProvisionalProposal,
+    PayjoinProposal
+);
+
+/// Where a [`ReceiverSession`] is in the BIP 77 flow.
+#[derive(uniffi::Enum)]
+pub enum ReceiverSessionState {
+    /// Waiting for the sender's Original PSBT.
+    Initialized,
+    UncheckedProposal {
+        proposal: Arc<UncheckedProposal>,
+    },
+    MaybeInputsOwned {
+        proposal: Arc<MaybeInputsOwned>,
+    },
+    MaybeInputsSeen {
+        proposal: Arc<MaybeInputsSeen>,
+    },
+    OutputsUnknown {
+        proposal: Arc<OutputsUnknown>,
+    },
+    WantsOutputs {
+        proposal: Arc<WantsOutputs>,
+    },
+    WantsInputs {
+        proposal: Arc<WantsInputs>,
+    },
+    ProvisionalProposal {
+        proposal: Arc<ProvisionalProposal>,
+    },
+    PayjoinProposal {
+        proposal: Arc<PayjoinProposal>,
+    },
+}
+
+impl From<super::ReceiverSessionState> for ReceiverSessionState {
+    fn from(value: super::ReceiverSessionState) -> Self {
+        use super::ReceiverSessionState as State;
+        match value {
+            State::Initialized => Self::Initialized,
+            State::UncheckedProposal(p) => Self::UncheckedProposal { proposal: Arc::new(p.into()) },
+            State::MaybeInputsOwned(p) => Self::MaybeInputsOwned { proposal: Arc::new(p.into()) },
+            State::MaybeInputsSeen(p) => Self::MaybeInputsSeen { proposal: Arc::new(p.into()) },
+            State::OutputsUnknown(p) => Self::OutputsUnknown { proposal: Arc::new(p.into()) },
+            State::WantsOutputs(p) => Self::WantsOutputs { proposal: Arc::new(p.into()) },
+            State::WantsInputs(p) => Self::WantsInputs { proposal: Arc::new(p.into()) },
+            State::ProvisionalProposal(p) => {
+                Self::ProvisionalProposal { proposal: Arc::new(p.into()) }
+            }
+            State::PayjoinProposal(p) => Self::PayjoinProposal { proposal: Arc::new(p.into()) },
+        }
+    }
+}
+
+/// Something that happened in a [`ReceiverSession`].
+///
+/// Events that advance the typestate carry the typestate they produced, so replaying the
+/// history reconstructs the current [`ReceiverSessionState`].
+#[derive(uniffi::Enum)]
+pub enum ReceiverSessionEvent {
+    Created,
+    /// The directory was polled for the sender's Original PSBT.
+    Polled,
+    ProposalReceived {
+        proposal: Arc<UncheckedProposal>,
+    },
+    BroadcastSuitabilityChecked {
+        proposal: Arc<MaybeInputsOwned>,
+    },
+    InputsNotOwnedChecked {
+        proposal: Arc<MaybeInputsSeen>,
+    },
+    NoInputsSeenBeforeChecked {
+        proposal: Arc<OutputsUnknown>,
+    },
+    ReceiverOutputsIdentified {
+        proposal: Arc<WantsOutputs>,
+    },
+    OutputsSubstituted {
+        proposal: Arc<WantsOutputs>,
+    },
+    OutputsCommitted {
+        proposal: Arc<WantsInputs>,
+    },
+    InputsContributed {
+        proposal: Arc<WantsInputs>,
+    },
+    InputsCommitted {
+        proposal: Arc<ProvisionalProposal>,
+    },
+    ProposalFinalized {
+        proposal: Arc<PayjoinProposal>,
+    },
+    /// The Payjoin Proposal was posted to the directory.
+    ProposalPosted,
+    CheckFailed {
+        check: ReceiverCheck,
+        error: String,
+    },
+    /// An error response was sent back to the sender.
+    ErrorReplied {
+        error: String,
+    },
+}
+
+impl From<super::ReceiverSessionEvent> for ReceiverSessionEvent {
+    fn from(value: super::ReceiverSessionEvent) -> Self {
+        use super::ReceiverSessionEvent as Event;
+        match value {
+            Event::Created => Self::Created,
+            Event::Polled => Self::Polled,
+            Event::ProposalReceived(p) => Self::ProposalReceived { proposal: Arc::new(p.into()) },
+            Event::BroadcastSuitabilityChecked(p) => {
+                Self::BroadcastSuitabilityChecked { proposal: Arc::new(p.into()) }
+            }
+            Event::InputsNotOwnedChecked(p) => {
+                Self::InputsNotOwnedChecked { proposal: Arc::new(p.into()) }
+            }
+            Event::NoInputsSeenBeforeChecked(p) => {
+                Self::NoInputsSeenBeforeChecked { proposal: Arc::new(p.into()) }
+            }
+            Event::ReceiverOutputsIdentified(p) => {
+                Self::ReceiverOutputsIdentified { proposal: Arc::new(p.into()) }
+            }
+            Event::OutputsSubstituted(p) => {
+                Self::OutputsSubstituted { proposal: Arc::new(p.into()) }
+            }
+            Event::OutputsCommitted(p) => Self::OutputsCommitted { proposal: Arc::new(p.into()) },
+            Event::InputsContributed(p) => Self::InputsContributed { proposal: Arc::new(p.into()) },
+            Event::InputsCommitted(p) => Self::InputsCommitted { proposal: Arc::new(p.into()) },
+            Event::ProposalFinalized(p) => Self::ProposalFinalized { proposal: Arc::new(p.into()) },
+            Event::ProposalPosted => Self::ProposalPosted,
+            Event::CheckFailed { check, error } => Self::CheckFailed { check, error },
+            Event::ErrorReplied { error } => Self::ErrorReplied { error },
+        }
+    }
+}
+
+/// A [`Receiver`] together with the append-only history of its session.
+///
+/// Advance the session through its own methods, which record each step, and save it after
+/// every step so it can be resumed exactly where it left off.
+#[derive(uniffi::Object)]
+pub struct ReceiverSession(Mutex<super::ReceiverSession>);
+
+impl From<super::ReceiverSession> for ReceiverSession {
+    fn from(value: super::ReceiverSession) -> Self {
+        Self(Mutex::new(value))
+    }
+}
+
+impl From<&ReceiverSession> for super::ReceiverSession {
+    fn from(value: &ReceiverSession) -> Self {
+        value.session().clone()
+    }
+}
+
+impl From<super::ReceiverSession> for ReceiverToken {
+    fn from(value: super::ReceiverSession) -> Self {
+        value.key().into()
+    }
+}
+
+impl ReceiverSession {
+    fn session(&self) -> MutexGuard<'_, super::ReceiverSession> {
+        // The session is only ever replaced whole, so a poisoned lock still holds a valid one
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run `step` on a copy of the session, so foreign callbacks run without the lock held,
+    /// then keep the copy unless the session advanced in the meantime.
+    fn step<T>(
+        &self,
+        name: &str,
+        step: impl FnOnce(&mut super::ReceiverSession) -> Result<T, ReceiverSessionError>,
+    ) -> Result<T, ReceiverSessionError> {
+        let mut advanced = self.session().clone();
+        let recorded = advanced.events.len();
+        let result = step(&mut advanced);
+        let mut session = self.session();
+        if session.events.len() != recorded {
+            return Err(session.wrong_state(name));
+        }
+        *session = advanced;
+        result
+    }
+}
+
+#[uniffi::export]
+impl ReceiverSession {
+    /// Start tracking a session that is waiting for the sender's Original PSBT.
+    #[uniffi::constructor]
+    pub fn new(receiver: Arc<Receiver>) -> Self {
+        super::ReceiverSession::new((*receiver).clone().into()).into()
+    }
+
+    /// Loads a [`ReceiverSession`] from the provided persister using the storage token.
+    #[uniffi::constructor]
+    pub fn load(
+        token: Arc<ReceiverToken>,
+        persister: Arc<dyn ReceiverPersister>,
+    ) -> Result<Self, PersistenceError> {
+        Ok(super::ReceiverSession::from(persister.load(token)?.as_ref()).into())
+    }
+
+    /// Saves the session using the provided persister and returns the storage token.
+    pub fn persist(
+        &self,
+        persister: Arc<dyn ReceiverPersister>,
+    ) -> Result<ReceiverToken, ImplementationError> {
+        let mut adapter = CallbackPersisterAdapter::new(persister);
+        self.session().persist(&mut adapter)
+    }
+
+    pub fn receiver(&self) -> Receiver {
+        self.session().receiver().into()
+    }
+
+    pub fn state(&self) -> ReceiverSessionState {
+        self.session().state().into()
+    }
+
+    pub fn events(&self) -> Vec<ReceiverSessionEvent> {
+        self.session().events().into_iter().map(Into::into).collect()
+    }
+
+    /// The session history with timestamps, for display or logging.
+    pub fn history(&self) -> Vec<SessionEventRecord> {
+        self.session().history()
+    }
+
+    /// Process the directory's response to a poll, see [`Receiver::process_res`].
+    pub fn process_res(
+        &self,
+        body: &[u8],
+        context: Arc<ClientResponse>,
+    ) -> Result<Option<Arc<UncheckedProposal>>, ReceiverSessionError> {
+        self.step("poll", |session| session.process_res(body, &context))
+            .map(|p| p.map(|p| Arc::new(p.into())))
+    }
+
+    /// See [`UncheckedProposal::check_broadcast_suitability`].
+    pub fn check_broadcast_suitability(
+        &self,
+        min_fee_rate: Option<Arc<FeeRate>>,
+        can_broadcast: Arc<dyn CanBroadcast>,
+    ) -> Result<Arc<MaybeInputsOwned>, ReceiverSessionError> {
+        self.step("check broadcast suitability", |session| {
+            session.check_broadcast_suitability(min_fee_rate.map(|r| (*r).clone()), |transaction| {
+                can_broadcast
+                    .callback(transaction.to_vec())
+                    .map_err(|e| ImplementationError::from(e.to_string()))
+            })
+        })
+        .map(|p| Arc::new(p.into()))
+    }
+
+    /// See [`UncheckedProposal::assume_interactive_receiver`].
+    pub fn assume_interactive_receiver(
+        &self,
+    ) -> Result<Arc<MaybeInputsOwned>, ReceiverSessionError> {
+        self.step("assume an interactive receiver", |session| session.assume_interactive_receiver())
+            .map(|p| Arc::new(p.into()))
+    }
+
+    /// See [`MaybeInputsOwned::check_inputs_not_owned`].
+    pub fn check_inputs_not_owned(
+        &self,
+        is_owned: Arc<dyn IsScriptOwned>,
+    ) -> Result<Arc<MaybeInputsSeen>, ReceiverSessionError> {
+        self.step("check inputs not owned", |session| {
+            session.check_inputs_not_owned(|input| {
+                is_owned
+                    .callback(input.to_vec())
+                    .map_err(|e| ImplementationError::from(e.to_string()))
+            })
+        })
+        .map(|p| Arc::new(p.into()))
+    }
+
+    /// See [`MaybeInputsSeen::check_no_inputs_seen_before`].
+    pub fn check_no_inputs_seen_before(
+        &self,
+        is_known: Arc<dyn IsOutputKnown>,
+    ) -> Result<Arc<OutputsUnknown>, ReceiverSessionError> {
+        self.step("check no inputs seen before", |session| {
+            session.check_no_inputs_seen_before(|outpoint| {
+                is_known
+                    .callback(outpoint.clone())
+                    .map_err(|e| ImplementationError::from(e.to_string()))
+            })
+        })
+        .map(|p| Arc::new(p.into()))
+    }
+
+    /// See [`OutputsUnknown::identify_receiver_outputs`].
+    pub fn identify_receiver_outputs(
+        &self,
+        is_receiver_output: Arc<dyn IsScriptOwned>,
+    ) -> Result<Arc<WantsOutputs>, ReceiverSessionError> {
+        self.step("identify receiver outputs", |session| {
+            session.identify_receiver_outputs(|output_script| {
+                is_receiver_output
+                    .callback(output_script.to_vec())
+                    .map_err(|e| ImplementationError::from(e.to_string()))
+            })
+        })
+        .map(|p| Arc::new(p.into()))
+    }
+
+    /// See [`WantsOutputs::replace_receiver_outputs`].
+    pub fn replace_receiver_outputs(
+        &self,
+        replacement_outputs: Vec<TxOut>,
+        drain_script: Arc<Script>,
+    ) -> Result<Arc<WantsOutputs>, ReceiverSessionError> {
+        self.step("replace receiver outputs", |session| {
+            session.replace_receiver_outputs(replacement_outputs, &drain_script)
+        })
+        .map(|p| Arc::new(p.into()))
+    }
+
+    /// See [`WantsOutputs::substitute_receiver_script`].
+    pub fn substitute_receiver_script(
+        &self,
+        output_script: Arc<Script>,
+    ) -> Result<Arc<WantsOutputs>, ReceiverSessionError> {
+        self.step("substitute the receiver script", |session| {
+            session.substitute_receiver_script(&output_script)
+        })
+        .map(|p| Arc::new(p.into()))
+    }
+
+    /// See [`WantsOutputs::commit_outputs`].
+    pub fn commit_outputs(&self) -> Result<Arc<WantsInputs>, ReceiverSessionError> {
+        self.step("commit outputs", |session| session.commit_outputs()).map(|p| Arc::new(p.into()))
+    }
+
+    /// See [`WantsInputs::contribute_inputs`].
+    pub fn contribute_inputs(
+        &self,
+        replacement_inputs: Vec<Arc<InputPair>>,
+    ) -> Result<Arc<WantsInputs>, ReceiverSessionError> {
+        let replacement_inputs: Vec<InputPair> = replacement_inputs
+            .into_iter()
+            .map(|pair| Arc::try_unwrap(pair).unwrap_or_else(|arc| (*arc).clone()))
+            .collect();
+        self.step("contribute inputs", |session| session.contribute_inputs(replacement_inputs))
+            .map(|p| Arc::new(p.into()))
+    }
+
+    /// See [`WantsInputs::commit_inputs`].
+    pub fn commit_inputs(&self) -> Result<Arc<ProvisionalProposal>, ReceiverSessionError> {
+        self.step("commit inputs", |session| session.commit_inputs()).map(|p| Arc::new(p.into()))
+    }
+
+    /// See [`ProvisionalProposal::finalize_proposal`].
+    pub fn finalize_proposal(
+        &self,
+        process_psbt: Arc<dyn ProcessPsbt>,
+        min_fee_rate: Option<Arc<FeeRate>>,
+        max_effective_fee_rate: Option<Arc<FeeRate>>,
+    ) -> Result<Arc<PayjoinProposal>, ReceiverSessionError> {
+        self.step("finalize the proposal", |session| {
+            session.finalize_proposal(
+                |psbt| {
+                    process_psbt
+                        .callback(psbt.to_string())
+                        .map_err(|e| ImplementationError::from(e.to_string()))
+                },
+                min_fee_rate.map(|r| (*r).clone()),
+                max_effective_fee_rate.map(|r| (*r).clone()),
+            )
+        })
+        .map(|p| Arc::new(p.into()))
+    }
+
+    /// Process the directory's response to posting the Payjoin Proposal, see
+    /// [`PayjoinProposal::process_res`].
+    pub fn process_proposal_res(
+        &self,
+        body: &[u8],
+        context: Arc<ClientResponse>,
+    ) -> Result<(), ReceiverSessionError> {
+        self.step("post the proposal", |session| session.process_proposal_res(body, &context))
+    }
+
+    /// Process the directory's response to posting `reply` to the sender, see
+    /// [`UncheckedProposal::process_err_res`].
+    pub fn process_err_res(
+        &self,
+        reply: Arc<JsonReply>,
+        body: &[u8],
+        context: Arc<ClientResponse>,
+    ) -> Result<(), ReceiverSessionError> {
+        self.step("reply with an error", |session| session.process_err_res(&reply, body, &context))
+    }
+
+    pub fn to_json(&self) -> Result<String, SerdeJsonError> {
+        self.session().to_json()
+    }
+
+    #[uniffi::constructor]
+    pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+        super::ReceiverSession::from_json(json).map(Into::into)
+    }
+
+    pub fn key(&self) -> ReceiverToken {
+        self.session().key().into()
     }
 }
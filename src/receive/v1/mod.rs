@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use payjoin::bitcoin::psbt::Psbt;
+
+use super::{
+    ImplementationError, InputContributionError, InputPair, OutputSubstitutionError,
+    ReplyableError, SelectionError,
+};
+use crate::bitcoin_ffi::{FeeRate, OutPoint, Script, TxOut};
+use crate::OutputSubstitution;
+
+#[cfg(feature = "uniffi")]
+pub mod uni;
+
+/// The HTTP headers of an incoming BIP 78 request.
+///
+/// Header names are matched case-insensitively.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct Headers(HashMap<String, String>);
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl Headers {
+    /// Build headers from the raw header map of the incoming request.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn new(headers: HashMap<String, String>) -> Self {
+        Self(headers.into_iter().map(|(k, v)| (k.to_ascii_lowercase(), v)).collect())
+    }
+
+    /// Build the minimal `text/plain` headers expected for the given request body.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn from_vec(body: Vec<u8>) -> Self {
+        let mut h = HashMap::new();
+        h.insert("content-type".to_string(), "text/plain".to_string());
+        h.insert("content-length".to_string(), body.len().to_string());
+        Self(h)
+    }
+
+    pub fn get_map(&self) -> HashMap<String, String> {
+        self.0.clone()
+    }
+}
+
+impl payjoin::receive::v1::Headers for Headers {
+    fn get_header(&self, key: &str) -> Option<&str> {
+        self.0.get(&key.to_ascii_lowercase()).map(|e| e.as_str())
+    }
+}
+
+/// The sender’s original PSBT and optional parameters, as received over a BIP 78 endpoint.
+#[derive(Clone)]
+pub struct UncheckedProposal(payjoin::receive::v1::UncheckedProposal);
+
+impl From<payjoin::receive::v1::UncheckedProposal> for UncheckedProposal {
+    fn from(value: payjoin::receive::v1::UncheckedProposal) -> Self {
+        Self(value)
+    }
+}
+
+impl From<UncheckedProposal> for payjoin::receive::v1::UncheckedProposal {
+    fn from(value: UncheckedProposal) -> Self {
+        value.0
+    }
+}
+
+impl UncheckedProposal {
+    /// Parse the body, query string and headers of an incoming BIP 78 request.
+    pub fn from_request(
+        body: Vec<u8>,
+        query: String,
+        headers: Arc<Headers>,
+    ) -> Result<Self, ReplyableError> {
+        payjoin::receive::v1::UncheckedProposal::from_request(
+            body.as_slice(),
+            query.as_str(),
+            (*headers).clone(),
+        )
+        .map(Into::into)
+        .map_err(Into::into)
+    }
+
+    ///The Sender’s Original PSBT
+    pub fn extract_tx_to_schedule_broadcast(&self) -> Vec<u8> {
+        payjoin::bitcoin::consensus::encode::serialize(
+            &self.0.clone().extract_tx_to_schedule_broadcast(),
+        )
+    }
+
+    pub fn check_broadcast_suitability(
+        &self,
+        min_fee_rate: Option<FeeRate>,
+        can_broadcast: impl Fn(&Vec<u8>) -> Result<bool, ImplementationError>,
+    ) -> Result<MaybeInputsOwned, ReplyableError> {
+        self.0
+            .clone()
+            .check_broadcast_suitability(min_fee_rate.map(Into::into), |transaction| {
+                Ok(can_broadcast(&payjoin::bitcoin::consensus::encode::serialize(transaction))?)
+            })
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    /// Call this method if the only way to initiate a Payjoin with this receiver
+    /// requires manual intervention, as in most consumer wallets.
+    ///
+    /// So-called "non-interactive" receivers, like payment processors, that allow arbitrary requests are otherwise vulnerable to probing attacks.
+    /// Those receivers call `extract_tx_to_check_broadcast()` and `attest_tested_and_scheduled_broadcast()` after making those checks downstream.
+    pub fn assume_interactive_receiver(&self) -> MaybeInputsOwned {
+        self.0.clone().assume_interactive_receiver().into()
+    }
+}
+
+#[derive(Clone)]
+pub struct MaybeInputsOwned(payjoin::receive::v1::MaybeInputsOwned);
+
+impl From<payjoin::receive::v1::MaybeInputsOwned> for MaybeInputsOwned {
+    fn from(value: payjoin::receive::v1::MaybeInputsOwned) -> Self {
+        Self(value)
+    }
+}
+
+impl MaybeInputsOwned {
+    pub fn check_inputs_not_owned(
+        &self,
+        is_owned: impl Fn(&Vec<u8>) -> Result<bool, ImplementationError>,
+    ) -> Result<MaybeInputsSeen, ReplyableError> {
+        self.0
+            .clone()
+            .check_inputs_not_owned(|input| Ok(is_owned(&input.to_bytes())?))
+            .map_err(Into::into)
+            .map(Into::into)
+    }
+}
+
+#[derive(Clone)]
+pub struct MaybeInputsSeen(payjoin::receive::v1::MaybeInputsSeen);
+
+impl From<payjoin::receive::v1::MaybeInputsSeen> for MaybeInputsSeen {
+    fn from(value: payjoin::receive::v1::MaybeInputsSeen) -> Self {
+        Self(value)
+    }
+}
+
+impl MaybeInputsSeen {
+    pub fn check_no_inputs_seen_before(
+        &self,
+        is_known: impl Fn(&OutPoint) -> Result<bool, ImplementationError>,
+    ) -> Result<OutputsUnknown, ReplyableError> {
+        self.0
+            .clone()
+            .check_no_inputs_seen_before(|outpoint| Ok(is_known(&(*outpoint).into())?))
+            .map_err(Into::into)
+            .map(Into::into)
+    }
+}
+
+/// The receiver has not yet identified which outputs belong to the receiver.
+///
+/// Only accept PSBTs that send us money.
+/// Identify those outputs with `identify_receiver_outputs()` to proceed
+#[derive(Clone)]
+pub struct OutputsUnknown(payjoin::receive::v1::OutputsUnknown);
+
+impl From<payjoin::receive::v1::OutputsUnknown> for OutputsUnknown {
+    fn from(value: payjoin::receive::v1::OutputsUnknown) -> Self {
+        Self(value)
+    }
+}
+
+impl OutputsUnknown {
+    /// Find which outputs belong to the receiver
+    pub fn identify_receiver_outputs(
+        &self,
+        is_receiver_output: impl Fn(&Vec<u8>) -> Result<bool, ImplementationError>,
+    ) -> Result<WantsOutputs, ReplyableError> {
+        self.0
+            .clone()
+            .identify_receiver_outputs(|input| Ok(is_receiver_output(&input.to_bytes())?))
+            .map_err(Into::into)
+            .map(Into::into)
+    }
+}
+
+#[derive(Clone)]
+pub struct WantsOutputs(payjoin::receive::v1::WantsOutputs);
+
+impl From<payjoin::receive::v1::WantsOutputs> for WantsOutputs {
+    fn from(value: payjoin::receive::v1::WantsOutputs) -> Self {
+        Self(value)
+    }
+}
+
+impl WantsOutputs {
+    pub fn output_substitution(&self) -> OutputSubstitution {
+        self.0.output_substitution()
+    }
+
+    pub fn replace_receiver_outputs(
+        &self,
+        replacement_outputs: Vec<TxOut>,
+        drain_script: &Script,
+    ) -> Result<WantsOutputs, OutputSubstitutionError> {
+        let replacement_outputs: Vec<payjoin::bitcoin::TxOut> =
+            replacement_outputs.iter().map(|o| o.clone().into()).collect();
+        self.0
+            .clone()
+            .replace_receiver_outputs(replacement_outputs, &drain_script.0)
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn substitute_receiver_script(
+        &self,
+        output_script: &Script,
+    ) -> Result<WantsOutputs, OutputSubstitutionError> {
+        self.0
+            .clone()
+            .substitute_receiver_script(&output_script.0)
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn commit_outputs(&self) -> WantsInputs {
+        self.0.clone().commit_outputs().into()
+    }
+}
+
+#[derive(Clone)]
+pub struct WantsInputs(payjoin::receive::v1::WantsInputs);
+
+impl From<payjoin::receive::v1::WantsInputs> for WantsInputs {
+    fn from(value: payjoin::receive::v1::WantsInputs) -> Self {
+        Self(value)
+    }
+}
+
+impl WantsInputs {
+    /// Select receiver input such that the payjoin avoids surveillance.
+    /// Return the input chosen that has been applied to the Proposal.
+    ///
+    /// See [`crate::receive::WantsInputs::try_preserving_privacy`].
+    pub fn try_preserving_privacy(
+        &self,
+        candidate_inputs: Vec<InputPair>,
+    ) -> Result<InputPair, SelectionError> {
+        match self.0.clone().try_preserving_privacy(candidate_inputs.into_iter().map(Into::into)) {
+            Ok(t) => Ok(t.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn contribute_inputs(
+        &self,
+        replacement_inputs: Vec<InputPair>,
+    ) -> Result<WantsInputs, InputContributionError> {
+        self.0
+            .clone()
+            .contribute_inputs(replacement_inputs.into_iter().map(Into::into))
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    pub fn commit_inputs(&self) -> ProvisionalProposal {
+        self.0.clone().commit_inputs().into()
+    }
+}
+
+#[derive(Clone)]
+pub struct ProvisionalProposal(payjoin::receive::v1::ProvisionalProposal);
+
+impl From<payjoin::receive::v1::ProvisionalProposal> for ProvisionalProposal {
+    fn from(value: payjoin::receive::v1::ProvisionalProposal) -> Self {
+        Self(value)
+    }
+}
+
+impl ProvisionalProposal {
+    pub fn finalize_proposal(
+        &self,
+        process_psbt: impl Fn(String) -> Result<String, ImplementationError>,
+        min_fee_rate: Option<FeeRate>,
+        max_effective_fee_rate: Option<FeeRate>,
+    ) -> Result<PayjoinProposal, ReplyableError> {
+        self.0
+            .clone()
+            .finalize_proposal(
+                |pre_processed| {
+                    let psbt = process_psbt(pre_processed.to_string())?;
+                    Ok(Psbt::from_str(&psbt)?)
+                },
+                min_fee_rate.map(Into::into),
+                max_effective_fee_rate.map(Into::into),
+            )
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+}
+
+#[derive(Clone)]
+pub struct PayjoinProposal(payjoin::receive::v1::PayjoinProposal);
+
+impl From<PayjoinProposal> for payjoin::receive::v1::PayjoinProposal {
+    fn from(value: PayjoinProposal) -> Self {
+        value.0
+    }
+}
+
+impl From<payjoin::receive::v1::PayjoinProposal> for PayjoinProposal {
+    fn from(value: payjoin::receive::v1::PayjoinProposal) -> Self {
+        Self(value)
+    }
+}
+
+impl PayjoinProposal {
+    pub fn utxos_to_be_locked(&self) -> Vec<OutPoint> {
+        self.0.utxos_to_be_locked().map(|o| (*o).into()).collect()
+    }
+
+    /// The Payjoin Proposal PSBT, base64 encoded.
+    ///
+    /// This is the `text/plain` body of the BIP 78 response to return to the sender.
+    pub fn psbt(&self) -> String {
+        self.0.psbt().to_string()
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+
+    fn get_proposal_from_test_vector() -> Result<UncheckedProposal, ReplyableError> {
+        // OriginalPSBT Test Vector from BIP
+        // | InputScriptType | Orginal PSBT Fee rate | maxadditionalfeecontribution | additionalfeeoutputindex|
+        // |-----------------|-----------------------|------------------------------|-------------------------|
+        // | P2SH-P2WPKH     |  2 sat/vbyte          | 0.00000182                   | 0                       |
+        let original_psbt =
+            "cHNidP8BAHMCAAAAAY8nutGgJdyYGXWiBEb45Hoe9lWGbkxh/6bNiOJdCDuDAAAAAAD+////AtyVuAUAAAAAF6kUHehJ8GnSdBUOOv6ujXLrWmsJRDCHgIQeAAAAAAAXqRR3QJbbz0hnQ8IvQ0fptGn+votneofTAAAAAAEBIKgb1wUAAAAAF6kU3k4ekGHKWRNbA1rV5tR5kEVDVNCHAQcXFgAUx4pFclNVgo1WWAdN1SYNX8tphTABCGsCRzBEAiB8Q+A6dep+Rz92vhy26lT0AjZn4PRLi8Bf9qoB/CMk0wIgP/Rj2PWZ3gEjUkTlhDRNAQ0gXwTO7t9n+V14pZ6oljUBIQMVmsAaoNWHVMS02LfTSe0e388LNitPa1UQZyOihY+FFgABABYAFEb2Giu6c4KO5YW0pfw3lGp9jMUUAAA=";
+        let body = original_psbt.as_bytes();
+
+        let headers = Headers::from_vec(body.to_vec());
+        UncheckedProposal::from_request(
+            body.to_vec(),
+            "?maxadditionalfeecontribution=182?additionalfeeoutputindex=0".to_string(),
+            Arc::new(headers),
+        )
+    }
+
+    #[test]
+    fn can_get_proposal_from_request() {
+        let proposal = get_proposal_from_test_vector();
+        assert!(proposal.is_ok(), "OriginalPSBT should be a valid request");
+    }
+
+    #[test]
+    fn headers_are_case_insensitive() {
+        let headers =
+            Headers::new(HashMap::from([("Content-Type".to_string(), "text/plain".to_string())]));
+        assert_eq!(
+            payjoin::receive::v1::Headers::get_header(&headers, "content-type"),
+            Some("text/plain")
+        );
+    }
+
+    #[test]
+    fn unchecked_proposal_unlocks_after_checks() {
+        let proposal = get_proposal_from_test_vector().unwrap();
+        let payjoin = proposal
+            .assume_interactive_receiver()
+            .check_inputs_not_owned(|_| Ok(false))
+            .expect("No inputs should be owned")
+            .check_no_inputs_seen_before(|_| Ok(false))
+            .expect("No inputs should be seen before")
+            .identify_receiver_outputs(|script| {
+                let network = payjoin::bitcoin::Network::Bitcoin;
+                let script = payjoin::bitcoin::ScriptBuf::from_bytes(script.to_vec());
+                Ok(payjoin::bitcoin::Address::from_script(&script, network).unwrap()
+                    == payjoin::bitcoin::Address::from_str("3CZZi7aWFugaCdUCS15dgrUUViupmB8bVM")
+                        .map(|x| x.require_network(network).unwrap())
+                        .unwrap())
+            })
+            .expect("Receiver output should be identified")
+            .commit_outputs()
+            .commit_inputs()
+            .finalize_proposal(Ok, None, Some(FeeRate::from_sat_per_vb(1000).unwrap()))
+            .expect("Proposal should be finalized");
+        assert!(Psbt::from_str(&payjoin.psbt()).is_ok(), "v1 response body should be a PSBT");
+    }
+}
@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use super::Headers;
+use crate::bitcoin_ffi::{FeeRate, OutPoint, Script, TxOut};
+use crate::receive::uni::{CanBroadcast, IsOutputKnown, IsScriptOwned, ProcessPsbt};
+use crate::receive::{
+    ImplementationError, InputContributionError, InputPair, OutputSubstitutionError,
+    ReplyableError, SelectionError,
+};
+use crate::OutputSubstitution;
+
+/// The sender’s original PSBT and optional parameters, as received over a BIP 78 endpoint.
+///
+/// If you are implementing an interactive payment processor, you should get extract the original transaction with extract_tx_to_schedule_broadcast() and schedule, followed by checking that the transaction can be broadcast with check_broadcast_suitability. Otherwise it is safe to call assume_interactive_receiver to proceed with validation.
+#[derive(Clone, uniffi::Object)]
+pub struct V1UncheckedProposal(super::UncheckedProposal);
+
+impl From<super::UncheckedProposal> for V1UncheckedProposal {
+    fn from(value: super::UncheckedProposal) -> Self {
+        Self(value)
+    }
+}
+
+#[uniffi::export]
+impl V1UncheckedProposal {
+    /// Parse the body, query string and headers of an incoming BIP 78 request.
+    #[uniffi::constructor]
+    pub fn from_request(
+        body: Vec<u8>,
+        query: String,
+        headers: Arc<Headers>,
+    ) -> Result<Self, ReplyableError> {
+        super::UncheckedProposal::from_request(body, query, headers).map(Into::into)
+    }
+
+    /// The Sender’s Original PSBT
+    pub fn extract_tx_to_schedule_broadcast(&self) -> Vec<u8> {
+        self.0.extract_tx_to_schedule_broadcast()
+    }
+
+    /// Call after checking that the Original PSBT can be broadcast.
+    ///
+    /// Receiver MUST check that the Original PSBT from the sender can be broadcast, i.e. testmempoolaccept bitcoind rpc returns { “allowed”: true,.. } for get_transaction_to_check_broadcast() before calling this method.
+    pub fn check_broadcast_suitability(
+        &self,
+        min_fee_rate: Option<Arc<FeeRate>>,
+        can_broadcast: Arc<dyn CanBroadcast>,
+    ) -> Result<Arc<V1MaybeInputsOwned>, ReplyableError> {
+        self.0
+            .check_broadcast_suitability(min_fee_rate.map(|r| (*r).clone()), |transaction| {
+                can_broadcast
+                    .callback(transaction.to_vec())
+                    .map_err(|e| ImplementationError::from(e.to_string()))
+            })
+            .map(|e| Arc::new(e.into()))
+    }
+
+    /// Call this method if the only way to initiate a Payjoin with this receiver
+    /// requires manual intervention, as in most consumer wallets.
+    pub fn assume_interactive_receiver(&self) -> Arc<V1MaybeInputsOwned> {
+        Arc::new(self.0.assume_interactive_receiver().into())
+    }
+}
+
+/// Type state to validate that the Original PSBT has no receiver-owned inputs.
+#[derive(Clone, uniffi::Object)]
+pub struct V1MaybeInputsOwned(super::MaybeInputsOwned);
+
+impl From<super::MaybeInputsOwned> for V1MaybeInputsOwned {
+    fn from(value: super::MaybeInputsOwned) -> Self {
+        Self(value)
+    }
+}
+
+#[uniffi::export]
+impl V1MaybeInputsOwned {
+    ///Check that the Original PSBT has no receiver-owned inputs. Return original-psbt-rejected error or otherwise refuse to sign undesirable inputs.
+    /// An attacker could try to spend receiver's own inputs. This check prevents that.
+    pub fn check_inputs_not_owned(
+        &self,
+        is_owned: Arc<dyn IsScriptOwned>,
+    ) -> Result<Arc<V1MaybeInputsSeen>, ReplyableError> {
+        self.0
+            .check_inputs_not_owned(|input| {
+                is_owned
+                    .callback(input.to_vec())
+                    .map_err(|e| ImplementationError::from(e.to_string()))
+            })
+            .map(|t| Arc::new(t.into()))
+    }
+}
+
+/// Typestate to validate that the Original PSBT has no inputs that have been seen before.
+#[derive(Clone, uniffi::Object)]
+pub struct V1MaybeInputsSeen(super::MaybeInputsSeen);
+
+impl From<super::MaybeInputsSeen> for V1MaybeInputsSeen {
+    fn from(value: super::MaybeInputsSeen) -> Self {
+        Self(value)
+    }
+}
+
+#[uniffi::export]
+impl V1MaybeInputsSeen {
+    /// Make sure that the original transaction inputs have never been seen before. This prevents probing attacks. This prevents reentrant Payjoin, where a sender proposes a Payjoin PSBT as a new Original PSBT for a new Payjoin.
+    pub fn check_no_inputs_seen_before(
+        &self,
+        is_known: Arc<dyn IsOutputKnown>,
+    ) -> Result<Arc<V1OutputsUnknown>, ReplyableError> {
+        self.0
+            .check_no_inputs_seen_before(|outpoint| {
+                is_known
+                    .callback(outpoint.clone())
+                    .map_err(|e| ImplementationError::from(e.to_string()))
+            })
+            .map(|t| Arc::new(t.into()))
+    }
+}
+
+/// The receiver has not yet identified which outputs belong to the receiver.
+///
+/// Only accept PSBTs that send us money. Identify those outputs with identify_receiver_outputs() to proceed
+#[derive(Clone, uniffi::Object)]
+pub struct V1OutputsUnknown(super::OutputsUnknown);
+
+impl From<super::OutputsUnknown> for V1OutputsUnknown {
+    fn from(value: super::OutputsUnknown) -> Self {
+        Self(value)
+    }
+}
+
+#[uniffi::export]
+impl V1OutputsUnknown {
+    /// Find which outputs belong to the receiver
+    pub fn identify_receiver_outputs(
+        &self,
+        is_receiver_output: Arc<dyn IsScriptOwned>,
+    ) -> Result<Arc<V1WantsOutputs>, ReplyableError> {
+        self.0
+            .identify_receiver_outputs(|output_script| {
+                is_receiver_output
+                    .callback(output_script.to_vec())
+                    .map_err(|e| ImplementationError::from(e.to_string()))
+            })
+            .map(|t| Arc::new(t.into()))
+    }
+}
+
+#[derive(uniffi::Object)]
+pub struct V1WantsOutputs(super::WantsOutputs);
+
+impl From<super::WantsOutputs> for V1WantsOutputs {
+    fn from(value: super::WantsOutputs) -> Self {
+        Self(value)
+    }
+}
+
+#[uniffi::export]
+impl V1WantsOutputs {
+    pub fn output_substitution(&self) -> OutputSubstitution {
+        self.0.output_substitution()
+    }
+
+    pub fn replace_receiver_outputs(
+        &self,
+        replacement_outputs: Vec<TxOut>,
+        drain_script: Arc<Script>,
+    ) -> Result<Arc<V1WantsOutputs>, OutputSubstitutionError> {
+        self.0
+            .replace_receiver_outputs(replacement_outputs, &drain_script)
+            .map(|t| Arc::new(t.into()))
+    }
+
+    pub fn commit_outputs(&self) -> Arc<V1WantsInputs> {
+        Arc::new(self.0.commit_outputs().into())
+    }
+
+    pub fn substitute_receiver_script(
+        &self,
+        output_script: Arc<Script>,
+    ) -> Result<Arc<V1WantsOutputs>, OutputSubstitutionError> {
+        self.0.substitute_receiver_script(&output_script).map(|t| Arc::new(t.into()))
+    }
+}
+
+#[derive(uniffi::Object)]
+pub struct V1WantsInputs(super::WantsInputs);
+
+impl From<super::WantsInputs> for V1WantsInputs {
+    fn from(value: super::WantsInputs) -> Self {
+        Self(value)
+    }
+}
+
+#[uniffi::export]
+impl V1WantsInputs {
+    /// Select receiver input such that the payjoin avoids surveillance.
+    /// Return the input chosen that has been applied to the Proposal.
+    pub fn try_preserving_privacy(
+        &self,
+        candidate_inputs: Vec<Arc<InputPair>>,
+    ) -> Result<Arc<InputPair>, SelectionError> {
+        let candidate_inputs: Vec<InputPair> = candidate_inputs
+            .into_iter()
+            .map(|pair| Arc::try_unwrap(pair).unwrap_or_else(|arc| (*arc).clone()))
+            .collect();
+
+        self.0.try_preserving_privacy(candidate_inputs).map(Arc::new)
+    }
+
+    pub fn contribute_inputs(
+        &self,
+        replacement_inputs: Vec<Arc<InputPair>>,
+    ) -> Result<Arc<V1WantsInputs>, InputContributionError> {
+        let replacement_inputs: Vec<InputPair> = replacement_inputs
+            .into_iter()
+            .map(|pair| Arc::try_unwrap(pair).unwrap_or_else(|arc| (*arc).clone()))
+            .collect();
+        self.0.contribute_inputs(replacement_inputs).map(|t| Arc::new(t.into()))
+    }
+
+    pub fn commit_inputs(&self) -> Arc<V1ProvisionalProposal> {
+        Arc::new(self.0.commit_inputs().into())
+    }
+}
+
+/// A mutable checked proposal that the receiver may contribute inputs to to make a payjoin.
+#[derive(uniffi::Object)]
+pub struct V1ProvisionalProposal(super::ProvisionalProposal);
+
+impl From<super::ProvisionalProposal> for V1ProvisionalProposal {
+    fn from(value: super::ProvisionalProposal) -> Self {
+        Self(value)
+    }
+}
+
+#[uniffi::export]
+impl V1ProvisionalProposal {
+    pub fn finalize_proposal(
+        &self,
+        process_psbt: Arc<dyn ProcessPsbt>,
+        min_fee_rate: Option<Arc<FeeRate>>,
+        max_effective_fee_rate: Option<Arc<FeeRate>>,
+    ) -> Result<Arc<V1PayjoinProposal>, ReplyableError> {
+        self.0
+            .finalize_proposal(
+                |psbt| {
+                    process_psbt
+                        .callback(psbt.to_string())
+                        .map_err(|e| ImplementationError::from(e.to_string()))
+                },
+                min_fee_rate.map(|r| (*r).clone()),
+                max_effective_fee_rate.map(|r| (*r).clone()),
+            )
+            .map(|e| Arc::new(e.into()))
+    }
+}
+
+#[derive(Clone, uniffi::Object)]
+pub struct V1PayjoinProposal(super::PayjoinProposal);
+
+impl From<super::PayjoinProposal> for V1PayjoinProposal {
+    fn from(value: super::PayjoinProposal) -> Self {
+        Self(value)
+    }
+}
+
+#[uniffi::export]
+impl V1PayjoinProposal {
+    pub fn utxos_to_be_locked(&self) -> Vec<OutPoint> {
+        self.0.utxos_to_be_locked()
+    }
+
+    /// The Payjoin Proposal PSBT, base64 encoded.
+    ///
+    /// This is the `text/plain` body of the BIP 78 response to return to the sender.
+    pub fn psbt(&self) -> String {
+        self.0.psbt()
+    }
+}
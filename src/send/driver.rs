@@ -0,0 +1,196 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::{Sender, V2GetContext};
+use crate::transport::{Cancellation, PollConfig, SessionDriverError, Transport, TransportError};
+
+/// Progress callbacks invoked by a [`SenderSessionDriver`].
+#[cfg_attr(feature = "uniffi", uniffi::export(with_foreign))]
+pub trait SenderSessionObserver: Send + Sync {
+    /// Called once the Original PSBT has been posted to the receiver's directory mailbox.
+    fn on_original_psbt_sent(&self);
+    /// Called when the receiver has no v2 endpoint and the Original PSBT is sent over BIP 78.
+    fn on_fallback_to_v1(&self);
+    /// Called before each request for the Payjoin Proposal, starting at `attempt` 1.
+    fn on_poll(&self, attempt: u32);
+    /// Called when a poll failed with a transient error and will be retried after `backoff_ms`.
+    fn on_retry(&self, error: String, backoff_ms: u64);
+}
+
+/// How a [`SenderSessionDriver`] run ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+pub enum SenderSessionOutcome {
+    /// The receiver replied with a Payjoin Proposal PSBT, ready to be signed and broadcast.
+    Proposal { psbt: String },
+    /// The receiver did not reply before the timeout.
+    ///
+    /// The sender should broadcast the Original PSBT's transaction instead.
+    Timeout,
+}
+
+/// Drives a [`Sender`] from posting the Original PSBT until the Payjoin Proposal arrives.
+///
+/// The v2 flow is used when the payjoin URI carries a v2 endpoint; otherwise the driver falls
+/// back to a synchronous BIP 78 request.
+pub struct SenderSessionDriver {
+    sender: Sender,
+    transport: Transport,
+    config: PollConfig,
+    timeout: Duration,
+    observer: Option<Arc<dyn SenderSessionObserver>>,
+    cancellation: Cancellation,
+}
+
+impl SenderSessionDriver {
+    /// `timeout_secs` bounds the whole run, after which [`SenderSessionOutcome::Timeout`] is
+    /// returned.
+    pub fn new(
+        sender: Sender,
+        transport: Transport,
+        config: PollConfig,
+        timeout_secs: u64,
+        observer: Option<Arc<dyn SenderSessionObserver>>,
+    ) -> Self {
+        Self {
+            sender,
+            transport,
+            config,
+            timeout: Duration::from_secs(timeout_secs),
+            observer,
+            cancellation: Cancellation::default(),
+        }
+    }
+
+    /// Send the Original PSBT and wait for the receiver's Payjoin Proposal.
+    pub async fn run(&self) -> Result<SenderSessionOutcome, SessionDriverError> {
+        let deadline = Instant::now() + self.timeout;
+        // Only a v1 endpoint may receive the unencrypted Original PSBT
+        if !self.sender.has_v2_endpoint() {
+            return self.run_v1(deadline).await;
+        }
+        if self.sender.expires_at().is_some_and(|expiry| expiry <= crate::persist::unix_now()) {
+            return Err(SessionDriverError::Expired);
+        }
+        let get_ctx = match self.within(deadline, self.sender.send(&self.transport)).await? {
+            None => return Ok(SenderSessionOutcome::Timeout),
+            Some(get_ctx) => get_ctx?,
+        };
+        if let Some(observer) = &self.observer {
+            observer.on_original_psbt_sent();
+        }
+        self.poll_v2(get_ctx, deadline).await
+    }
+
+    async fn run_v1(&self, deadline: Instant) -> Result<SenderSessionOutcome, SessionDriverError> {
+        if let Some(observer) = &self.observer {
+            observer.on_fallback_to_v1();
+        }
+        let (req, ctx) = self.sender.extract_v1();
+        match self.within(deadline, self.transport.post_v1(req)).await? {
+            None => Ok(SenderSessionOutcome::Timeout),
+            Some(response) => {
+                let psbt = ctx.process_response(response?).map_err(TransportError::from)?;
+                Ok(SenderSessionOutcome::Proposal { psbt })
+            }
+        }
+    }
+
+    async fn poll_v2(
+        &self,
+        get_ctx: V2GetContext,
+        deadline: Instant,
+    ) -> Result<SenderSessionOutcome, SessionDriverError> {
+        let mut backoff = Duration::from_millis(self.config.initial_backoff_ms);
+        let max_backoff = Duration::from_millis(self.config.max_backoff_ms);
+        let mut attempt: u32 = 0;
+        loop {
+            attempt = attempt.saturating_add(1);
+            if let Some(observer) = &self.observer {
+                observer.on_poll(attempt);
+            }
+            match self.within(deadline, get_ctx.poll(&self.transport)).await? {
+                None => return Ok(SenderSessionOutcome::Timeout),
+                Some(Ok(Some(psbt))) => return Ok(SenderSessionOutcome::Proposal { psbt }),
+                Some(Ok(None)) => {}
+                Some(Err(e)) if e.is_transient() => {
+                    if let Some(observer) = &self.observer {
+                        observer.on_retry(e.to_string(), backoff.as_millis() as u64);
+                    }
+                }
+                Some(Err(e)) => return Err(e.into()),
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(SenderSessionOutcome::Timeout);
+            }
+            self.cancellation.sleep(backoff.min(remaining)).await?;
+            backoff = backoff.saturating_mul(2).min(max_backoff);
+        }
+    }
+
+    /// Run `fut` unless the driver is cancelled, returning `None` if `deadline` passes first.
+    async fn within<F: Future>(
+        &self,
+        deadline: Instant,
+        fut: F,
+    ) -> Result<Option<F::Output>, SessionDriverError> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match tokio::time::timeout(remaining, self.cancellation.run(fut)).await {
+            Ok(output) => output.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Stop a running [`SenderSessionDriver::run`], which then returns
+    /// [`SessionDriverError::Cancelled`].
+    pub fn cancel(&self) {
+        self.cancellation.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{
+        load_receiver, load_sender, new_receiver_for, new_sender_to, payee_uri, PAYEE_ADDRESS,
+    };
+    use crate::{PjUri, TransportConfig, Url};
+
+    fn driver(timeout_secs: u64) -> SenderSessionDriver {
+        driver_for(payee_uri(), timeout_secs)
+    }
+
+    fn driver_for(uri: PjUri, timeout_secs: u64) -> SenderSessionDriver {
+        let sender = load_sender(new_sender_to(uri));
+        let relay = Url::parse("https://relay.example.com".to_string()).unwrap();
+        let transport = Transport::new(Arc::new(relay), TransportConfig::default()).unwrap();
+        SenderSessionDriver::new(sender, transport, PollConfig::default(), timeout_secs, None)
+    }
+
+    #[tokio::test]
+    async fn cancelled_driver_stops_sending() {
+        let driver = driver(60);
+        driver.cancel();
+        assert!(matches!(driver.run().await, Err(SessionDriverError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn expired_v2_session_is_not_sent_over_v1() {
+        let uri = load_receiver(new_receiver_for(PAYEE_ADDRESS, Some(0))).pj_uri();
+        let driver = driver_for(uri, 60);
+        assert!(matches!(driver.run().await, Err(SessionDriverError::Expired)));
+    }
+
+    #[tokio::test]
+    async fn elapsed_timeout_yields_fallback_outcome() {
+        let driver = driver(0);
+        assert_eq!(driver.run().await.unwrap(), SenderSessionOutcome::Timeout);
+    }
+}
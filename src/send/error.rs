@@ -3,25 +3,111 @@ use std::sync::Arc;
 use payjoin::bitcoin::psbt::PsbtParseError;
 use payjoin::send;
 
+use crate::ohttp::ClientResponseError;
+
 /// Error building a Sender from a SenderBuilder.
 ///
 /// This error is unrecoverable.
 #[derive(Debug, PartialEq, Eq, thiserror::Error)]
-#[error("Error initializing the sender: {msg}")]
-#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
-pub struct BuildSenderError {
-    msg: String,
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
+pub enum BuildSenderError {
+    /// The Original PSBT could not be parsed.
+    #[error("Error parsing the Original PSBT: {msg}")]
+    PsbtParse { msg: String },
+    /// An input is missing UTXO information or is otherwise invalid.
+    #[error("An input in the Original PSBT is invalid: {msg}")]
+    InvalidOriginalInput { msg: String },
+    /// The PSBT fields do not match its unsigned transaction.
+    ///
+    /// A PSBT string is decoded with one map per transaction input and output, so
+    /// [`SenderBuilder::new`](super::SenderBuilder::new) reports mismatched counts as
+    /// [`BuildSenderError::PsbtParse`] instead.
+    #[error("The Original PSBT is inconsistent: {msg}")]
+    InconsistentOriginalPsbt { msg: String },
+    #[error("The Original PSBT has no inputs")]
+    NoInputs,
+    /// The payee output value differs from the amount in the payment URI.
+    #[error("The payee output value does not equal the amount requested in the URI")]
+    PayeeValueNotEqual,
+    /// A PSBT without outputs lacks a payee output too, which is checked first and
+    /// reported as [`BuildSenderError::MissingPayeeOutput`].
+    #[error("The Original PSBT has no outputs")]
+    NoOutputs,
+    #[error("The Original PSBT has more than one output paying the payee")]
+    MultiplePayeeOutputs,
+    #[error("The Original PSBT has no output paying the payee")]
+    MissingPayeeOutput,
+    /// The change output cannot cover the maximum fee contribution.
+    #[error("The change output value is lower than the maximum fee contribution")]
+    FeeOutputValueLowerThanFeeContribution,
+    /// There are more than two outputs, so the change output must be given explicitly.
+    #[error("Cannot determine the change output among more than two outputs")]
+    AmbiguousChangeOutput,
+    #[error("The change output index is out of bounds")]
+    ChangeIndexOutOfBounds,
+    #[error("The change output index points at the payee output")]
+    ChangeIndexPointsAtPayee,
+    #[error("Cannot determine the expected input weight: {msg}")]
+    InputWeight { msg: String },
+    #[error("Cannot determine the input address type: {msg}")]
+    AddressType { msg: String },
+    /// An error this version of the bindings does not recognize.
+    #[error("Error initializing the sender: {msg}")]
+    Unexpected { msg: String },
 }
 
 impl From<PsbtParseError> for BuildSenderError {
     fn from(value: PsbtParseError) -> Self {
-        BuildSenderError { msg: value.to_string() }
+        BuildSenderError::PsbtParse { msg: value.to_string() }
     }
 }
 
 impl From<send::BuildSenderError> for BuildSenderError {
     fn from(value: send::BuildSenderError) -> Self {
-        BuildSenderError { msg: value.to_string() }
+        BuildSenderError::from_message(value.to_string())
+    }
+}
+
+impl BuildSenderError {
+    fn from_message(msg: String) -> Self {
+        // The upstream variants are private, so they are told apart by their messages
+        let detail = |prefix: &str| msg.strip_prefix(prefix).map(str::to_string);
+        if let Some(msg) = detail("an input in the original transaction is invalid: ") {
+            return BuildSenderError::InvalidOriginalInput { msg };
+        }
+        if let Some(msg) = detail("the original transaction is inconsistent: ") {
+            return BuildSenderError::InconsistentOriginalPsbt { msg };
+        }
+        if let Some(msg) = detail("can not determine expected input weight: ") {
+            return BuildSenderError::InputWeight { msg };
+        }
+        if let Some(msg) = detail("can not determine input address type: ") {
+            return BuildSenderError::AddressType { msg };
+        }
+        match msg.as_str() {
+            "the original transaction has no inputs" => BuildSenderError::NoInputs,
+            "the value in original transaction doesn't equal value requested in the payment link" => {
+                BuildSenderError::PayeeValueNotEqual
+            }
+            "the original transaction has no outputs" => BuildSenderError::NoOutputs,
+            "the original transaction has more than one output belonging to the payee" => {
+                BuildSenderError::MultiplePayeeOutputs
+            }
+            "the output belonging to payee is missing from the original transaction" => {
+                BuildSenderError::MissingPayeeOutput
+            }
+            "the value of fee output is lower than maximum allowed contribution" => {
+                BuildSenderError::FeeOutputValueLowerThanFeeContribution
+            }
+            "can not determine which output is change because there's more than two outputs" => {
+                BuildSenderError::AmbiguousChangeOutput
+            }
+            "fee output index is points out of bounds" => BuildSenderError::ChangeIndexOutOfBounds,
+            "fee output index is points at output belonging to the payee" => {
+                BuildSenderError::ChangeIndexPointsAtPayee
+            }
+            _ => BuildSenderError::Unexpected { msg: msg.clone() },
+        }
     }
 }
 
@@ -39,7 +125,44 @@ pub struct CreateRequestError(#[from] send::v2::CreateRequestError);
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
-pub struct EncapsulationError(#[from] send::v2::EncapsulationError);
+pub struct EncapsulationError(InternalEncapsulationError);
+
+#[derive(Debug, thiserror::Error)]
+enum InternalEncapsulationError {
+    #[error(transparent)]
+    Encapsulation(send::v2::EncapsulationError),
+    #[error(transparent)]
+    ContextUsed(V2PostContextError),
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl EncapsulationError {
+    /// Whether the [`crate::send::V2PostContext`] passed in was already used.
+    pub fn is_context_used(&self) -> bool {
+        matches!(self.0, InternalEncapsulationError::ContextUsed(_))
+    }
+}
+
+impl From<send::v2::EncapsulationError> for EncapsulationError {
+    fn from(value: send::v2::EncapsulationError) -> Self {
+        Self(InternalEncapsulationError::Encapsulation(value))
+    }
+}
+
+impl From<V2PostContextError> for EncapsulationError {
+    fn from(value: V2PostContextError) -> Self {
+        Self(InternalEncapsulationError::ContextUsed(value))
+    }
+}
+
+/// The [`crate::send::V2PostContext`] was already used to process a response.
+///
+/// A response can only be processed once. To be able to retry, keep a copy made with
+/// `to_json` before the first attempt.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("V2PostContext was already used to process a response")]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct V2PostContextError;
 
 /// Error that may occur when the response from receiver is malformed.
 #[derive(Debug, thiserror::Error)]
@@ -47,6 +170,77 @@ pub struct EncapsulationError(#[from] send::v2::EncapsulationError);
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
 pub struct ValidationError(#[from] send::ValidationError);
 
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl ValidationError {
+    pub fn kind(&self) -> ValidationErrorKind {
+        ValidationErrorKind::from_message(&self.0.to_string())
+    }
+}
+
+/// What was wrong with the receiver's Payjoin Proposal.
+///
+/// Whatever the kind, the Proposal must not be signed. The sender should broadcast the
+/// Original PSBT's transaction instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+pub enum ValidationErrorKind {
+    /// The response could not be decoded as a PSBT.
+    Parse,
+    /// The v2 response could not be decapsulated.
+    Encapsulation,
+    /// The receiver took more fee from the sender than allowed.
+    FeeTooHigh,
+    /// The Proposal pays less fee than the Original PSBT or the minimum fee rate.
+    FeeTooLow,
+    /// The sender's inputs were removed, reordered or modified.
+    InputsChanged,
+    /// The sender's outputs were removed, reordered or decreased.
+    OutputsChanged,
+    /// The transaction version or lock time was changed.
+    TransactionChanged,
+    /// An input contributed by the receiver is invalid.
+    InvalidReceiverInput,
+    /// An error this version of the bindings does not recognize.
+    Other,
+}
+
+impl ValidationErrorKind {
+    fn from_message(msg: &str) -> Self {
+        // The upstream variants are private, so they are told apart by their messages
+        const KINDS: &[(&str, ValidationErrorKind)] = &[
+            ("v2 encapsulation", ValidationErrorKind::Encapsulation),
+            ("fee contribution exceeds", ValidationErrorKind::FeeTooHigh),
+            ("take fee contribution", ValidationErrorKind::FeeTooHigh),
+            ("fee contribution pays", ValidationErrorKind::FeeTooHigh),
+            ("fee of proposed transaction is lower", ValidationErrorKind::FeeTooLow),
+            ("fee rate of proposed transaction is below", ValidationErrorKind::FeeTooLow),
+            ("belonging to the receiver", ValidationErrorKind::InvalidReceiverInput),
+            ("invalid input address type", ValidationErrorKind::InvalidReceiverInput),
+            ("missing previous txout", ValidationErrorKind::InvalidReceiverInput),
+            ("expected input weight", ValidationErrorKind::InvalidReceiverInput),
+            ("inputs contain key paths", ValidationErrorKind::InvalidReceiverInput),
+            ("doesn't have any inputs", ValidationErrorKind::InputsChanged),
+            ("inputs of the sender", ValidationErrorKind::InputsChanged),
+            ("belonging to the sender", ValidationErrorKind::InputsChanged),
+            ("sequence number", ValidationErrorKind::InputsChanged),
+            ("mixed sequence", ValidationErrorKind::InputsChanged),
+            ("outputs of the sender", ValidationErrorKind::OutputsChanged),
+            ("non-fee output was decreased", ValidationErrorKind::OutputsChanged),
+            ("despite it being disallowed", ValidationErrorKind::OutputsChanged),
+            ("outputs contain key paths", ValidationErrorKind::OutputsChanged),
+            ("transaction version", ValidationErrorKind::TransactionChanged),
+            ("lock time", ValidationErrorKind::TransactionChanged),
+            ("couldn't decode", ValidationErrorKind::Parse),
+            ("couldn't read", ValidationErrorKind::Parse),
+            ("psbt error", ValidationErrorKind::Parse),
+        ];
+        KINDS
+            .iter()
+            .find(|(pattern, _)| msg.contains(pattern))
+            .map_or(ValidationErrorKind::Other, |(_, kind)| *kind)
+    }
+}
+
 /// Represent an error returned by Payjoin receiver.
 #[derive(Debug, thiserror::Error)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
@@ -71,6 +265,10 @@ pub enum ResponseError {
     /// [`BIP78::ReceiverWellKnownError`]: https://github.com/bitcoin/bips/blob/master/bip-0078.mediawiki#user-content-Receivers_well_known_errors
     #[error("An unrecognized error occurred")]
     Unrecognized { error_code: String, msg: String },
+
+    /// The OHTTP context passed in was already used.
+    #[error("{0}")]
+    ClientResponse(Arc<ClientResponseError>),
 }
 
 impl From<send::ResponseError> for ResponseError {
@@ -85,8 +283,142 @@ impl From<send::ResponseError> for ResponseError {
     }
 }
 
+impl From<ClientResponseError> for ResponseError {
+    fn from(value: ClientResponseError) -> Self {
+        ResponseError::ClientResponse(Arc::new(value))
+    }
+}
+
 /// A well-known error that can be safely displayed to end users.
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
 pub struct WellKnownError(#[from] send::WellKnownError);
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl WellKnownError {
+    pub fn code(&self) -> WellKnownErrorCode {
+        WellKnownErrorCode::from_code(&self.error_code())
+    }
+
+    /// The code as it appears in the receiver's reply, e.g. `not-enough-money`.
+    pub fn error_code(&self) -> String {
+        // The upstream code is private, so it is read from the `Debug` output, which spells
+        // it as the variant name of the kebab-case code
+        let debug = format!("{:?}", self.0);
+        let variant = debug.split_once("code: ").map_or("", |(_, rest)| rest);
+        let mut code = String::new();
+        for c in variant.chars().take_while(char::is_ascii_alphanumeric) {
+            if c.is_ascii_uppercase() && !code.is_empty() {
+                code.push('-');
+            }
+            code.push(c.to_ascii_lowercase());
+        }
+        code
+    }
+
+    /// The protocol versions the receiver supports, sent along with `version-unsupported`.
+    pub fn supported_versions(&self) -> Option<Vec<u64>> {
+        let msg = self.0.to_string();
+        let list = msg.split_once('[')?.1.split_once(']')?.0;
+        list.split(',').map(|v| v.trim().parse().ok()).collect()
+    }
+}
+
+/// The error codes defined in the [`BIP78::ReceiverWellKnownError`] spec.
+///
+/// [`BIP78::ReceiverWellKnownError`]: https://github.com/bitcoin/bips/blob/master/bip-0078.mediawiki#user-content-Receivers_well_known_errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+pub enum WellKnownErrorCode {
+    /// `unavailable`: the receiver cannot process the request right now.
+    Unavailable,
+    /// `not-enough-money`: the receiver could not contribute to the fee.
+    NotEnoughMoney,
+    /// `version-unsupported`: see [`WellKnownError::supported_versions`].
+    VersionUnsupported,
+    /// `original-psbt-rejected`: the receiver rejected the Original PSBT.
+    OriginalPsbtRejected,
+    /// A well-known error this version of the bindings does not recognize, with its code.
+    Other { code: String },
+}
+
+impl WellKnownErrorCode {
+    fn from_code(code: &str) -> Self {
+        match code {
+            "unavailable" => WellKnownErrorCode::Unavailable,
+            "not-enough-money" => WellKnownErrorCode::NotEnoughMoney,
+            "version-unsupported" => WellKnownErrorCode::VersionUnsupported,
+            "original-psbt-rejected" => WellKnownErrorCode::OriginalPsbtRejected,
+            code => WellKnownErrorCode::Other { code: code.to_string() },
+        }
+    }
+
+    /// The code as it appears in the receiver's reply, e.g. `not-enough-money`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            WellKnownErrorCode::Unavailable => "unavailable",
+            WellKnownErrorCode::NotEnoughMoney => "not-enough-money",
+            WellKnownErrorCode::VersionUnsupported => "version-unsupported",
+            WellKnownErrorCode::OriginalPsbtRejected => "original-psbt-rejected",
+            WellKnownErrorCode::Other { code } => code,
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn build_errors_are_told_apart_by_their_message() {
+        let cases = [
+            ("the original transaction has no inputs", BuildSenderError::NoInputs),
+            ("the original transaction has no outputs", BuildSenderError::NoOutputs),
+            (
+                "the original transaction has more than one output belonging to the payee",
+                BuildSenderError::MultiplePayeeOutputs,
+            ),
+            (
+                "the original transaction is inconsistent: unequal input counts",
+                BuildSenderError::InconsistentOriginalPsbt {
+                    msg: "unequal input counts".to_string(),
+                },
+            ),
+            (
+                "can not determine expected input weight: unknown script",
+                BuildSenderError::InputWeight { msg: "unknown script".to_string() },
+            ),
+            (
+                "can not determine input address type: unknown script",
+                BuildSenderError::AddressType { msg: "unknown script".to_string() },
+            ),
+        ];
+        for (msg, expected) in cases {
+            assert_eq!(BuildSenderError::from_message(msg.to_string()), expected, "{msg}");
+        }
+    }
+
+    #[test]
+    fn unknown_well_known_codes_keep_their_code() {
+        for code in
+            ["unavailable", "not-enough-money", "version-unsupported", "original-psbt-rejected"]
+        {
+            let parsed = WellKnownErrorCode::from_code(code);
+            assert!(!matches!(parsed, WellKnownErrorCode::Other { .. }), "{code}");
+            assert_eq!(parsed.as_str(), code);
+        }
+        let parsed = WellKnownErrorCode::from_code("some-new-code");
+        assert_eq!(parsed, WellKnownErrorCode::Other { code: "some-new-code".to_string() });
+        assert_eq!(parsed.as_str(), "some-new-code");
+    }
+
+    #[test]
+    fn unrecognized_build_errors_keep_their_message() {
+        assert_eq!(
+            BuildSenderError::from_message("something new".to_string()),
+            BuildSenderError::Unexpected { msg: "something new".to_string() }
+        );
+    }
+}
@@ -2,16 +2,23 @@ use std::io::Cursor;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
-pub use error::{BuildSenderError, CreateRequestError, EncapsulationError, ResponseError};
+pub use error::{
+    BuildSenderError, CreateRequestError, EncapsulationError, ResponseError, V2PostContextError,
+    ValidationErrorKind, WellKnownErrorCode,
+};
 use payjoin::persist::{Persister, Value};
 use payjoin::send::v2::SenderToken;
 
-pub use crate::error::SerdeJsonError;
+use crate::bitcoin_ffi::{Amount, FeeRate};
+pub use crate::error::{ContextSerializationError, PersistenceError, SerdeJsonError};
 use crate::ohttp::ClientResponse;
+use crate::persist::{LoggedEvent, SessionEventRecord};
 use crate::receive::ImplementationError;
 use crate::request::Request;
 use crate::uri::{PjUri, Url};
 
+#[cfg(feature = "transport")]
+pub mod driver;
 pub mod error;
 #[cfg(feature = "uniffi")]
 pub mod uni;
@@ -53,10 +60,10 @@ impl SenderBuilder {
     // The minfeerate parameter is set if the contribution is available in change.
     //
     // This method fails if no recommendation can be made or if the PSBT is malformed.
-    pub fn build_recommended(&self, min_fee_rate: u64) -> Result<NewSender, BuildSenderError> {
+    pub fn build_recommended(&self, min_fee_rate: FeeRate) -> Result<NewSender, BuildSenderError> {
         self.0
             .clone()
-            .build_recommended(payjoin::bitcoin::FeeRate::from_sat_per_kwu(min_fee_rate))
+            .build_recommended(min_fee_rate.into())
             .map(|e| e.into())
             .map_err(|e| e.into())
     }
@@ -75,17 +82,17 @@ impl SenderBuilder {
     /// be just lowered in the request to match the change amount.
     pub fn build_with_additional_fee(
         &self,
-        max_fee_contribution: u64,
+        max_fee_contribution: Amount,
         change_index: Option<u8>,
-        min_fee_rate: u64,
+        min_fee_rate: FeeRate,
         clamp_fee_contribution: bool,
     ) -> Result<NewSender, BuildSenderError> {
         self.0
             .clone()
             .build_with_additional_fee(
-                payjoin::bitcoin::Amount::from_sat(max_fee_contribution),
+                max_fee_contribution.into(),
                 change_index.map(|x| x as usize),
-                payjoin::bitcoin::FeeRate::from_sat_per_kwu(min_fee_rate),
+                min_fee_rate.into(),
                 clamp_fee_contribution,
             )
             .map(|e| e.into())
@@ -97,13 +104,9 @@ impl SenderBuilder {
     /// This function disables contribution.
     pub fn build_non_incentivizing(
         &self,
-        min_fee_rate: u64,
+        min_fee_rate: FeeRate,
     ) -> Result<NewSender, BuildSenderError> {
-        match self
-            .0
-            .clone()
-            .build_non_incentivizing(payjoin::bitcoin::FeeRate::from_sat_per_kwu(min_fee_rate))
-        {
+        match self.0.clone().build_non_incentivizing(min_fee_rate.into()) {
             Ok(e) => Ok(e.into()),
             Err(e) => Err(e.into()),
         }
@@ -146,10 +149,11 @@ impl Sender {
     pub fn load<P: Persister<payjoin::send::v2::Sender>>(
         token: P::Token,
         persister: &P,
-    ) -> Result<Self, ImplementationError> {
-        let sender =
-            payjoin::send::v2::Sender::load(token, persister).map_err(ImplementationError::from)?;
-        Ok(sender.into())
+    ) -> Result<Self, PersistenceError>
+    where
+        PersistenceError: From<P::Error>,
+    {
+        persister.load(token).map(Into::into).map_err(Into::into)
     }
 
     pub fn extract_v1(&self) -> (Request, V1Context) {
@@ -179,6 +183,19 @@ impl Sender {
     pub fn key(&self) -> SenderToken {
         self.0.key()
     }
+
+    /// When the receiver's session expires, in seconds since the Unix epoch.
+    ///
+    /// Returns `None` for v1 endpoints, which carry no expiration.
+    pub fn expires_at(&self) -> Option<u64> {
+        crate::uri::endpoint_expiry(self.0.endpoint()).map(crate::persist::unix_secs)
+    }
+
+    /// Whether the receiver's endpoint carries v2 parameters, so the Original PSBT must only be
+    /// sent encapsulated.
+    pub(crate) fn has_v2_endpoint(&self) -> bool {
+        crate::uri::is_v2_endpoint(self.0.endpoint())
+    }
 }
 
 /// Data required for validation of response.
@@ -203,6 +220,10 @@ impl V1Context {
     }
 }
 
+/// The context of an Original PSBT posted to the receiver's directory mailbox.
+///
+/// It is consumed by [`V2PostContext::process_response`], but can be serialized beforehand so
+/// the response can still be processed if the app is suspended while the request is in flight.
 pub struct V2PostContext(Mutex<Option<payjoin::send::v2::V2PostContext>>);
 
 impl V2PostContext {
@@ -210,17 +231,27 @@ impl V2PostContext {
     /// Call this method with response from receiver to continue BIP-??? flow. A successful response can either be None if the relay has not response yet or Some(Psbt).
     /// If the response is some valid PSBT you should sign and broadcast.
     pub fn process_response(&self, response: &[u8]) -> Result<V2GetContext, EncapsulationError> {
-        <&V2PostContext as Into<payjoin::send::v2::V2PostContext>>::into(self)
-            .process_response(response)
-            .map(Into::into)
-            .map_err(Into::into)
+        self.take()?.process_response(response).map(Into::into).map_err(Into::into)
     }
-}
 
-impl From<&V2PostContext> for payjoin::send::v2::V2PostContext {
-    fn from(value: &V2PostContext) -> Self {
-        let mut data_guard = value.0.lock().unwrap();
-        Option::take(&mut *data_guard).expect("ContextV2 moved out of memory")
+    /// Move the context out, failing if it was already used.
+    fn take(&self) -> Result<payjoin::send::v2::V2PostContext, V2PostContextError> {
+        // A poisoned lock still guards a valid `Option`
+        let mut data_guard = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        data_guard.take().ok_or(V2PostContextError)
+    }
+
+    /// Fails if the context was already consumed by [`V2PostContext::process_response`].
+    pub fn to_json(&self) -> Result<String, ContextSerializationError> {
+        let data_guard = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let ctx = data_guard.as_ref().ok_or(V2PostContextError)?;
+        Ok(serde_json::to_string(ctx)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+        serde_json::from_str::<payjoin::send::v2::V2PostContext>(json)
+            .map_err(Into::into)
+            .map(Into::into)
     }
 }
 
@@ -230,6 +261,26 @@ impl From<payjoin::send::v2::V2PostContext> for V2PostContext {
     }
 }
 
+/// A consumed context serializes as `null` and is restored consumed, so recording a context in
+/// a [`SenderSession`] never makes it reusable.
+impl serde::Serialize for V2PostContext {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data_guard = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        serde::Serialize::serialize(&*data_guard, serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for V2PostContext {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ctx = Option::<payjoin::send::v2::V2PostContext>::deserialize(deserializer)?;
+        Ok(Self(Mutex::new(ctx)))
+    }
+}
+
+/// The context for polling the receiver's directory mailbox for the Payjoin Proposal.
+///
+/// Store it once the Original PSBT is posted so polling can resume after a restart.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct V2GetContext(payjoin::send::v2::V2GetContext);
 
 impl From<payjoin::send::v2::V2GetContext> for V2GetContext {
@@ -257,10 +308,683 @@ impl V2GetContext {
         response: &[u8],
         ohttp_ctx: &ClientResponse,
     ) -> Result<Option<String>, ResponseError> {
-        match self.0.process_response(response, ohttp_ctx.into()) {
+        match self.0.process_response(response, ohttp_ctx.take()?) {
             Ok(Some(psbt)) => Ok(Some(psbt.to_string())),
             Ok(None) => Ok(None),
             Err(e) => Err(e.into()),
         }
     }
+
+    pub fn to_json(&self) -> Result<String, SerdeJsonError> {
+        serde_json::to_string(self).map_err(Into::into)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+        serde_json::from_str(json).map_err(Into::into)
+    }
+}
+
+/// Where a [`SenderSession`] is in the BIP 77 flow.
+#[derive(Clone)]
+pub enum SenderSessionState {
+    /// The Original PSBT has not been sent yet.
+    Initialized,
+    /// The Original PSBT request was extracted and the directory's response has not been
+    /// processed yet.
+    ///
+    /// Resend the request or process its response with the context.
+    PostingOriginalPsbt(Arc<V2PostContext>),
+    /// The Original PSBT was posted and the receiver has not replied yet.
+    ///
+    /// Resume polling with the context.
+    AwaitingProposal(V2GetContext),
+    /// The receiver replied with a Payjoin Proposal PSBT.
+    ProposalReceived { psbt: String },
+}
+
+/// Something that happened in a [`SenderSession`].
+///
+/// Replaying the history reconstructs the current [`SenderSessionState`].
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub enum SenderSessionEvent {
+    Created,
+    /// The Original PSBT request was extracted, holding the same [`V2PostContext`] returned to
+    /// the caller.
+    OriginalPsbtSent(Arc<V2PostContext>),
+    /// The Original PSBT was posted to the receiver's directory mailbox.
+    OriginalPsbtPosted(V2GetContext),
+    /// The receiver has no v2 endpoint and the Original PSBT was sent over BIP 78.
+    ///
+    /// A BIP 78 request cannot outlive the connection, so this does not change the state.
+    FellBackToV1,
+    /// The directory was polled for the Payjoin Proposal.
+    Polled,
+    ProposalReceived {
+        psbt: String,
+    },
+    /// The receiver's response failed validation.
+    ResponseRejected {
+        error: String,
+    },
+}
+
+impl SenderSessionEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::OriginalPsbtSent(_) => "original_psbt_sent",
+            Self::OriginalPsbtPosted(_) => "original_psbt_posted",
+            Self::FellBackToV1 => "fell_back_to_v1",
+            Self::Polled => "polled",
+            Self::ProposalReceived { .. } => "proposal_received",
+            Self::ResponseRejected { .. } => "response_rejected",
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match self {
+            Self::ResponseRejected { error } => Some(error.clone()),
+            _ => None,
+        }
+    }
+
+    /// Apply this event to `state`. Events that do not advance the session leave it as is.
+    fn apply(&self, state: SenderSessionState) -> SenderSessionState {
+        match self {
+            Self::OriginalPsbtSent(ctx) => SenderSessionState::PostingOriginalPsbt(ctx.clone()),
+            Self::OriginalPsbtPosted(ctx) => SenderSessionState::AwaitingProposal(ctx.clone()),
+            Self::ProposalReceived { psbt } => {
+                SenderSessionState::ProposalReceived { psbt: psbt.clone() }
+            }
+            _ => state,
+        }
+    }
+}
+
+/// A [`Sender`] together with the append-only history of its session.
+///
+/// Advance the session through its own methods, which record each step. Persisters store a
+/// [`Sender`] as the start of its session, under the same token.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SenderSession {
+    sender: payjoin::send::v2::Sender,
+    events: Vec<LoggedEvent<SenderSessionEvent>>,
+}
+
+impl From<SenderSession> for SenderToken {
+    fn from(value: SenderSession) -> Self {
+        value.key()
+    }
+}
+
+impl Value for SenderSession {
+    type Key = SenderToken;
+
+    fn key(&self) -> SenderToken {
+        self.sender.key()
+    }
+}
+
+impl SenderSession {
+    /// Start a session whose Original PSBT has not been sent yet.
+    pub fn new(sender: Sender) -> Self {
+        Self { sender: sender.into(), events: vec![LoggedEvent::now(SenderSessionEvent::Created)] }
+    }
+
+    /// Loads a [`SenderSession`] from the provided persister using the storage token.
+    pub fn load<P: Persister<SenderSession>>(
+        token: P::Token,
+        persister: &P,
+    ) -> Result<Self, PersistenceError>
+    where
+        PersistenceError: From<P::Error>,
+    {
+        persister.load(token).map_err(Into::into)
+    }
+
+    /// Saves the session using the provided persister and returns the storage token.
+    pub fn persist<P: Persister<SenderSession>>(
+        &self,
+        persister: &mut P,
+    ) -> Result<P::Token, ImplementationError> {
+        persister.save(self.clone()).map_err(|e| ImplementationError::from(e.to_string()))
+    }
+
+    pub fn sender(&self) -> Sender {
+        self.sender.clone().into()
+    }
+
+    fn record(&mut self, event: SenderSessionEvent) {
+        self.events.push(LoggedEvent::now(event));
+    }
+
+    /// Record the Payjoin Proposal PSBT a response carried, or why it was rejected.
+    fn record_response<T>(
+        &mut self,
+        result: Result<T, ResponseError>,
+        psbt: impl Fn(&T) -> Option<String>,
+    ) -> Result<T, ResponseError> {
+        match &result {
+            Ok(value) => {
+                if let Some(psbt) = psbt(value) {
+                    self.record(SenderSessionEvent::ProposalReceived { psbt });
+                }
+            }
+            Err(e) => self.record(SenderSessionEvent::ResponseRejected { error: e.to_string() }),
+        }
+        result
+    }
+
+    /// The current state, reconstructed by replaying the session history from the start.
+    pub fn state(&self) -> SenderSessionState {
+        self.events
+            .iter()
+            .fold(SenderSessionState::Initialized, |state, logged| logged.event.apply(state))
+    }
+
+    /// Extract the request posting the Original PSBT, see [`Sender::extract_v2`].
+    ///
+    /// The session records the returned context itself, so it is still single use: once it
+    /// processes a response, [`SenderSessionState::PostingOriginalPsbt`] holds a used context.
+    /// Save the session before posting the request to be able to process the response after a
+    /// restart.
+    pub fn extract_v2(
+        &mut self,
+        ohttp_relay: Url,
+    ) -> Result<(Request, Arc<V2PostContext>), CreateRequestError> {
+        let (req, ctx) = self.sender().extract_v2(ohttp_relay)?;
+        let ctx = Arc::new(ctx);
+        self.record(SenderSessionEvent::OriginalPsbtSent(ctx.clone()));
+        Ok((req, ctx))
+    }
+
+    /// Process the directory's response to posting the Original PSBT, see
+    /// [`V2PostContext::process_response`].
+    pub fn process_post_response(
+        &mut self,
+        ctx: &V2PostContext,
+        response: &[u8],
+    ) -> Result<V2GetContext, EncapsulationError> {
+        let get_ctx = ctx.process_response(response)?;
+        self.record(SenderSessionEvent::OriginalPsbtPosted(get_ctx.clone()));
+        Ok(get_ctx)
+    }
+
+    /// Process the directory's response to a poll for the Payjoin Proposal, see
+    /// [`V2GetContext::process_response`].
+    pub fn process_get_response(
+        &mut self,
+        ctx: &V2GetContext,
+        response: &[u8],
+        ohttp_ctx: &ClientResponse,
+    ) -> Result<Option<String>, ResponseError> {
+        let result = ctx.process_response(response, ohttp_ctx);
+        if result.is_ok() {
+            self.record(SenderSessionEvent::Polled);
+        }
+        self.record_response(result, Option::clone)
+    }
+
+    /// Process the receiver's BIP 78 response, see [`V1Context::process_response`].
+    pub fn process_v1_response(
+        &mut self,
+        ctx: &V1Context,
+        response: Vec<u8>,
+    ) -> Result<String, ResponseError> {
+        self.record(SenderSessionEvent::FellBackToV1);
+        let result = ctx.process_response(response);
+        self.record_response(result, |psbt| Some(psbt.clone()))
+    }
+
+    pub fn events(&self) -> Vec<SenderSessionEvent> {
+        self.events.iter().map(|logged| logged.event.clone()).collect()
+    }
+
+    /// The session history with timestamps, for display or logging.
+    pub fn history(&self) -> Vec<SessionEventRecord> {
+        self.events
+            .iter()
+            .map(|logged| {
+                SessionEventRecord {
+                    timestamp: logged.timestamp,
+                    name: logged.event.name().to_string(),
+                    detail: logged.event.detail(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, SerdeJsonError> {
+        serde_json::to_string(self).map_err(Into::into)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+        serde_json::from_str(json).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{
+        load_sender, new_sender, v2_sender, InMemoryPersister, ORIGINAL_PSBT, PAYEE_URI,
+    };
+    use crate::Uri;
+
+    #[test]
+    fn load_persisted_sender() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_sender().persist(&mut persister).unwrap();
+        assert!(Sender::load(token, &persister).is_ok());
+    }
+
+    #[test]
+    fn load_missing_sender_is_not_found() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_sender().persist(&mut persister).unwrap();
+        persister.values.clear();
+        assert!(matches!(Sender::load(token, &persister), Err(PersistenceError::NotFound { .. })));
+    }
+
+    #[test]
+    fn load_corrupted_sender_is_deserialization_error() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_sender().persist(&mut persister).unwrap();
+        persister.values.insert(token.to_string(), "not json".to_string());
+        assert!(matches!(
+            Sender::load(token, &persister),
+            Err(PersistenceError::Deserialization { .. })
+        ));
+    }
+
+    #[test]
+    fn load_from_failing_backend_is_backend_error() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_sender().persist(&mut persister).unwrap();
+        persister.unavailable = true;
+        assert!(matches!(Sender::load(token, &persister), Err(PersistenceError::Backend { .. })));
+    }
+
+    #[test]
+    fn sender_session_replays_history() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_sender().persist(&mut persister).unwrap();
+        let mut session = SenderSession::new(Sender::load(token, &persister).unwrap());
+        assert!(matches!(session.state(), SenderSessionState::Initialized));
+
+        // A BIP 78 request cannot be resumed, so falling back leaves the state unchanged
+        session.record(SenderSessionEvent::FellBackToV1);
+        assert!(matches!(session.state(), SenderSessionState::Initialized));
+        session.record(SenderSessionEvent::ProposalReceived { psbt: ORIGINAL_PSBT.to_string() });
+
+        let resumed = SenderSession::from_json(&session.to_json().unwrap()).unwrap();
+        assert!(matches!(
+            resumed.state(),
+            SenderSessionState::ProposalReceived { psbt } if psbt == ORIGINAL_PSBT
+        ));
+        let names: Vec<String> = resumed.history().into_iter().map(|record| record.name).collect();
+        assert_eq!(names, ["created", "fell_back_to_v1", "proposal_received"]);
+    }
+
+    #[test]
+    fn rejected_v1_response_is_recorded() {
+        let mut persister = InMemoryPersister::default();
+        let token = new_sender().persist(&mut persister).unwrap();
+        let mut session = SenderSession::new(Sender::load(token, &persister).unwrap());
+        let (_, ctx) = session.sender().extract_v1();
+
+        let reply = r#"{"errorCode":"unavailable","message":"Receiver is offline"}"#;
+        assert!(session.process_v1_response(&ctx, reply.as_bytes().to_vec()).is_err());
+        let names: Vec<String> = session.history().into_iter().map(|record| record.name).collect();
+        assert_eq!(names, ["created", "fell_back_to_v1", "response_rejected"]);
+        assert!(matches!(session.state(), SenderSessionState::Initialized));
+    }
+
+    #[test]
+    fn v2_post_context_round_trips_until_consumed() {
+        let relay = Url::parse("https://relay.example.com".to_string()).unwrap();
+        let (_, ctx) = v2_sender().extract_v2(relay).unwrap();
+        let restored = V2PostContext::from_json(&ctx.to_json().unwrap()).unwrap();
+        assert!(restored.process_response(b"not an ohttp response").is_err());
+        assert!(matches!(restored.to_json(), Err(ContextSerializationError::V2PostContextUsed(_))));
+        let err = restored.process_response(b"not an ohttp response").unwrap_err();
+        assert!(err.is_context_used());
+    }
+
+    #[test]
+    fn post_context_recorded_in_the_session_is_single_use() {
+        let mut session = SenderSession::new(v2_sender());
+        let relay = Url::parse("https://relay.example.com".to_string()).unwrap();
+        let (_, ctx) = session.extract_v2(relay).unwrap();
+        let saved = session.to_json().unwrap();
+
+        assert!(ctx.process_response(b"not an ohttp response").is_err());
+        let SenderSessionState::PostingOriginalPsbt(recorded) = session.state() else {
+            panic!("expected the session to be posting the Original PSBT");
+        };
+        assert!(Arc::ptr_eq(&ctx, &recorded));
+        assert!(recorded.process_response(b"not an ohttp response").unwrap_err().is_context_used());
+
+        // A session saved before the response was processed resumes with an unused context
+        let resumed = SenderSession::from_json(&saved).unwrap();
+        let SenderSessionState::PostingOriginalPsbt(restored) = resumed.state() else {
+            panic!("expected the session to be posting the Original PSBT");
+        };
+        let err = restored.process_response(b"not an ohttp response").unwrap_err();
+        assert!(!err.is_context_used());
+
+        // Once used, the context is saved and restored as used
+        let resumed = SenderSession::from_json(&session.to_json().unwrap()).unwrap();
+        let SenderSessionState::PostingOriginalPsbt(restored) = resumed.state() else {
+            panic!("expected the session to be posting the Original PSBT");
+        };
+        assert!(restored.process_response(b"not an ohttp response").unwrap_err().is_context_used());
+    }
+
+    #[test]
+    fn undecodable_post_context_fails_to_load_the_session() {
+        let mut session = SenderSession::new(v2_sender());
+        let relay = Url::parse("https://relay.example.com".to_string()).unwrap();
+        session.extract_v2(relay).unwrap();
+
+        let mut json: serde_json::Value =
+            serde_json::from_str(&session.to_json().unwrap()).unwrap();
+        json["events"][1]["event"]["OriginalPsbtSent"] = serde_json::json!("not a context");
+        assert!(SenderSession::from_json(&json.to_string()).is_err());
+    }
+
+    /// The error from building a sender for `psbt` and `uri` with `build`.
+    fn build_error(
+        psbt: &str,
+        uri: &str,
+        build: impl FnOnce(SenderBuilder) -> Result<NewSender, BuildSenderError>,
+    ) -> BuildSenderError {
+        let uri = Uri::parse(uri.to_string()).unwrap().check_pj_supported().unwrap();
+        match SenderBuilder::new(psbt.to_string(), uri).and_then(build) {
+            Ok(_) => panic!("building the sender should fail"),
+            Err(e) => e,
+        }
+    }
+
+    fn non_incentivizing(builder: SenderBuilder) -> Result<NewSender, BuildSenderError> {
+        builder.build_non_incentivizing(FeeRate::from_sat_per_kwu(1000))
+    }
+
+    /// Contribute `sats` from the output at `change_index` without clamping.
+    fn additional_fee(
+        sats: u64,
+        change_index: Option<u8>,
+    ) -> impl FnOnce(SenderBuilder) -> Result<NewSender, BuildSenderError> {
+        move |builder| {
+            builder.build_with_additional_fee(
+                Amount::from_sat(sats),
+                change_index,
+                FeeRate::from_sat_per_kwu(1000),
+                false,
+            )
+        }
+    }
+
+    fn original_psbt() -> payjoin::bitcoin::Psbt {
+        payjoin::bitcoin::Psbt::from_str(ORIGINAL_PSBT).unwrap()
+    }
+
+    #[test]
+    fn unparseable_psbt_is_a_parse_error() {
+        let uri = Uri::parse(PAYEE_URI.to_string()).unwrap().check_pj_supported().unwrap();
+        assert!(matches!(
+            SenderBuilder::new("not a psbt".to_string(), uri),
+            Err(BuildSenderError::PsbtParse { .. })
+        ));
+    }
+
+    #[test]
+    fn input_without_utxo_is_invalid() {
+        let mut psbt = original_psbt();
+        psbt.inputs[0].witness_utxo = None;
+        assert!(matches!(
+            build_error(&psbt.to_string(), PAYEE_URI, non_incentivizing),
+            BuildSenderError::InvalidOriginalInput { .. }
+        ));
+    }
+
+    #[test]
+    fn payee_output_must_match_the_uri() {
+        let other_payee =
+            "bitcoin:tb1q6d3a2w975yny0asuvd9a67ner4nks58ff0q8g4?pj=https://example.com/pj";
+        assert_eq!(
+            build_error(ORIGINAL_PSBT, other_payee, non_incentivizing),
+            BuildSenderError::MissingPayeeOutput
+        );
+        let other_amount =
+            "bitcoin:2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK?amount=1&pj=https://example.com/pj";
+        assert_eq!(
+            build_error(ORIGINAL_PSBT, other_amount, non_incentivizing),
+            BuildSenderError::PayeeValueNotEqual
+        );
+    }
+
+    #[test]
+    fn fee_contribution_needs_a_usable_change_output() {
+        // Output 0 pays the payee and output 1 is change worth 2,000,000 sats
+        assert_eq!(
+            build_error(ORIGINAL_PSBT, PAYEE_URI, additional_fee(1000, Some(2))),
+            BuildSenderError::ChangeIndexOutOfBounds
+        );
+        assert_eq!(
+            build_error(ORIGINAL_PSBT, PAYEE_URI, additional_fee(1000, Some(0))),
+            BuildSenderError::ChangeIndexPointsAtPayee
+        );
+        assert_eq!(
+            build_error(ORIGINAL_PSBT, PAYEE_URI, additional_fee(3_000_000, Some(1))),
+            BuildSenderError::FeeOutputValueLowerThanFeeContribution
+        );
+
+        let mut psbt = original_psbt();
+        let extra_output = psbt.unsigned_tx.output[1].clone();
+        psbt.unsigned_tx.output.push(extra_output);
+        psbt.outputs.push(Default::default());
+        assert_eq!(
+            build_error(&psbt.to_string(), PAYEE_URI, additional_fee(1000, None)),
+            BuildSenderError::AmbiguousChangeOutput
+        );
+    }
+
+    #[test]
+    fn original_psbt_must_pay_the_payee_once() {
+        let mut psbt = original_psbt();
+        let payee_output = psbt.unsigned_tx.output[0].clone();
+        psbt.unsigned_tx.output.push(payee_output);
+        psbt.outputs.push(Default::default());
+        assert_eq!(
+            build_error(&psbt.to_string(), PAYEE_URI, non_incentivizing),
+            BuildSenderError::MultiplePayeeOutputs
+        );
+    }
+
+    #[test]
+    fn original_psbt_must_have_inputs() {
+        let mut psbt = original_psbt();
+        psbt.unsigned_tx.input.clear();
+        psbt.inputs.clear();
+        let recommended =
+            |builder: SenderBuilder| builder.build_recommended(FeeRate::from_sat_per_kwu(1000));
+        assert_eq!(
+            build_error(&psbt.to_string(), PAYEE_URI, recommended),
+            BuildSenderError::NoInputs
+        );
+    }
+
+    #[test]
+    fn original_psbt_without_outputs_misses_the_payee() {
+        let mut psbt = original_psbt();
+        psbt.unsigned_tx.output.clear();
+        psbt.outputs.clear();
+        // The payee output is looked for before the outputs are counted
+        assert_eq!(
+            build_error(&psbt.to_string(), PAYEE_URI, additional_fee(1000, None)),
+            BuildSenderError::MissingPayeeOutput
+        );
+    }
+
+    #[test]
+    fn psbt_maps_must_match_the_unsigned_transaction() {
+        let mut psbt = original_psbt();
+        psbt.outputs.pop();
+        // The maps are decoded per transaction output, so the mismatch never reaches the builder
+        assert!(matches!(
+            build_error(&psbt.to_string(), PAYEE_URI, non_incentivizing),
+            BuildSenderError::PsbtParse { .. }
+        ));
+    }
+
+    #[test]
+    fn input_weight_must_be_predictable() {
+        // A P2SH input without its redeem script may not be a nested P2WPKH spend
+        let mut psbt = original_psbt();
+        psbt.inputs[0].final_script_sig = None;
+        let recommended =
+            |builder: SenderBuilder| builder.build_recommended(FeeRate::from_sat_per_kwu(1000));
+        assert!(matches!(
+            build_error(&psbt.to_string(), PAYEE_URI, recommended),
+            BuildSenderError::InputWeight { .. }
+        ));
+    }
+
+    #[test]
+    fn input_address_type_must_be_known() {
+        // The first input's weight is predicted, the address type of the others is compared to it
+        let mut psbt = original_psbt();
+        let mut txin = psbt.unsigned_tx.input[0].clone();
+        txin.previous_output.vout += 1;
+        psbt.unsigned_tx.input.push(txin);
+        let mut input = psbt.inputs[0].clone();
+        input.witness_utxo.as_mut().unwrap().script_pubkey = payjoin::bitcoin::ScriptBuf::new();
+        psbt.inputs.push(input);
+        let recommended =
+            |builder: SenderBuilder| builder.build_recommended(FeeRate::from_sat_per_kwu(1000));
+        assert!(matches!(
+            build_error(&psbt.to_string(), PAYEE_URI, recommended),
+            BuildSenderError::AddressType { .. }
+        ));
+    }
+
+    fn v1_response_error(response: &str) -> ResponseError {
+        let sender = load_sender(new_sender());
+        let (_, ctx) = sender.extract_v1();
+        ctx.process_response(response.as_bytes().to_vec()).unwrap_err()
+    }
+
+    #[test]
+    fn well_known_errors_expose_their_code() {
+        let reply = r#"{"errorCode":"version-unsupported","supported":[1,2],"message":"v3"}"#;
+        let ResponseError::WellKnown(e) = v1_response_error(reply) else {
+            panic!("expected a well-known error");
+        };
+        assert_eq!(e.code(), WellKnownErrorCode::VersionUnsupported);
+        assert_eq!(e.code().as_str(), "version-unsupported");
+        assert_eq!(e.supported_versions(), Some(vec![1, 2]));
+
+        let reply = r#"{"errorCode":"not-enough-money","message":"Wallet is empty"}"#;
+        let ResponseError::WellKnown(e) = v1_response_error(reply) else {
+            panic!("expected a well-known error");
+        };
+        assert_eq!(e.code(), WellKnownErrorCode::NotEnoughMoney);
+        assert_eq!(e.supported_versions(), None);
+
+        for code in ["unavailable", "not-enough-money", "original-psbt-rejected"] {
+            let reply = format!(r#"{{"errorCode":"{code}","message":"Try again later"}}"#);
+            let ResponseError::WellKnown(e) = v1_response_error(&reply) else {
+                panic!("expected a well-known error");
+            };
+            assert_eq!(e.error_code(), code);
+            assert_eq!(e.code().as_str(), code);
+        }
+    }
+
+    /// The kind of error the sender of [`ORIGINAL_PSBT`] rejects `proposal` with.
+    fn proposal_error_kind(proposal: &str) -> ValidationErrorKind {
+        let ResponseError::Validation(e) = v1_response_error(proposal) else {
+            panic!("expected a validation error");
+        };
+        e.kind()
+    }
+
+    /// [`ORIGINAL_PSBT`] as a receiver returns it, without the sender's UTXO and signatures.
+    fn unchanged_proposal() -> payjoin::bitcoin::Psbt {
+        let mut psbt = original_psbt();
+        psbt.inputs[0] = Default::default();
+        psbt
+    }
+
+    #[test]
+    fn malformed_proposal_is_a_parse_error() {
+        assert_eq!(proposal_error_kind("not a psbt"), ValidationErrorKind::Parse);
+    }
+
+    #[test]
+    fn changed_transaction_is_rejected() {
+        let mut proposal = unchanged_proposal();
+        proposal.unsigned_tx.lock_time = payjoin::bitcoin::absolute::LockTime::ZERO;
+        assert_eq!(
+            proposal_error_kind(&proposal.to_string()),
+            ValidationErrorKind::TransactionChanged
+        );
+    }
+
+    #[test]
+    fn changed_sender_inputs_are_rejected() {
+        let mut proposal = unchanged_proposal();
+        proposal.unsigned_tx.input[0].sequence = payjoin::bitcoin::Sequence::MAX;
+        assert_eq!(proposal_error_kind(&proposal.to_string()), ValidationErrorKind::InputsChanged);
+
+        // The sender's input must not be returned signed
+        assert_eq!(proposal_error_kind(ORIGINAL_PSBT), ValidationErrorKind::InputsChanged);
+    }
+
+    #[test]
+    fn invalid_receiver_inputs_are_rejected() {
+        use payjoin::bitcoin::bip32::{DerivationPath, Fingerprint};
+
+        let mut proposal = unchanged_proposal();
+        let mut txin = proposal.unsigned_tx.input[0].clone();
+        txin.previous_output.vout += 1;
+        proposal.unsigned_tx.input.push(txin);
+        proposal.inputs.push(Default::default());
+        assert_eq!(
+            proposal_error_kind(&proposal.to_string()),
+            ValidationErrorKind::InvalidReceiverInput
+        );
+
+        let key = "03159ac01aa0d58754c4b4d8b7d349ed1edfcf0b362b4f6b55106723a2858f8516";
+        proposal.inputs[1]
+            .bip32_derivation
+            .insert(key.parse().unwrap(), (Fingerprint::from([0; 4]), DerivationPath::master()));
+        assert_eq!(
+            proposal_error_kind(&proposal.to_string()),
+            ValidationErrorKind::InvalidReceiverInput
+        );
+    }
+
+    #[test]
+    fn changed_sender_outputs_are_rejected() {
+        let mut proposal = unchanged_proposal();
+        proposal.unsigned_tx.output.pop();
+        proposal.outputs.pop();
+        assert_eq!(proposal_error_kind(&proposal.to_string()), ValidationErrorKind::OutputsChanged);
+    }
+
+    #[test]
+    fn proposal_fees_are_checked() {
+        // The sender contributes at most 364 sats from the change at output 1
+        let mut proposal = unchanged_proposal();
+        proposal.unsigned_tx.output[1].value -= payjoin::bitcoin::Amount::from_sat(10_000);
+        assert_eq!(proposal_error_kind(&proposal.to_string()), ValidationErrorKind::FeeTooHigh);
+
+        // Paying the payee more lowers the 332 sat fee of the Original PSBT
+        let mut proposal = unchanged_proposal();
+        proposal.unsigned_tx.output[0].value += payjoin::bitcoin::Amount::from_sat(100);
+        assert_eq!(proposal_error_kind(&proposal.to_string()), ValidationErrorKind::FeeTooLow);
+    }
 }
@@ -1,8 +1,12 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
-use crate::error::ForeignError;
+use payjoin::persist::Value;
+
+use crate::bitcoin_ffi::{Amount, FeeRate};
+use crate::persist::{SessionEventRecord, SessionMetadata};
 pub use crate::send::{
-    BuildSenderError, CreateRequestError, EncapsulationError, ResponseError, SerdeJsonError,
+    BuildSenderError, ContextSerializationError, CreateRequestError, EncapsulationError,
+    PersistenceError, ResponseError, SerdeJsonError,
 };
 use crate::{ClientResponse, ImplementationError, PjUri, Request, Url};
 
@@ -47,8 +51,11 @@ impl SenderBuilder {
     // The minfeerate parameter is set if the contribution is available in change.
     //
     // This method fails if no recommendation can be made or if the PSBT is malformed.
-    pub fn build_recommended(&self, min_fee_rate: u64) -> Result<Arc<NewSender>, BuildSenderError> {
-        self.0.build_recommended(min_fee_rate).map(|e| Arc::new(e.into()))
+    pub fn build_recommended(
+        &self,
+        min_fee_rate: Arc<FeeRate>,
+    ) -> Result<Arc<NewSender>, BuildSenderError> {
+        self.0.build_recommended((*min_fee_rate).clone()).map(|e| Arc::new(e.into()))
     }
 
     /// Offer the receiver contribution to pay for his input.
@@ -66,16 +73,16 @@ impl SenderBuilder {
     /// be just lowered in the request to match the change amount.
     pub fn build_with_additional_fee(
         &self,
-        max_fee_contribution: u64,
+        max_fee_contribution: Arc<Amount>,
         change_index: Option<u8>,
-        min_fee_rate: u64,
+        min_fee_rate: Arc<FeeRate>,
         clamp_fee_contribution: bool,
     ) -> Result<Arc<NewSender>, BuildSenderError> {
         self.0
             .build_with_additional_fee(
-                max_fee_contribution,
+                (*max_fee_contribution).clone(),
                 change_index,
-                min_fee_rate,
+                (*min_fee_rate).clone(),
                 clamp_fee_contribution,
             )
             .map(|e| Arc::new(e.into()))
@@ -86,9 +93,9 @@ impl SenderBuilder {
     /// This function disables contribution.
     pub fn build_non_incentivizing(
         &self,
-        min_fee_rate: u64,
+        min_fee_rate: Arc<FeeRate>,
     ) -> Result<Arc<NewSender>, BuildSenderError> {
-        self.0.build_non_incentivizing(min_fee_rate).map(|e| Arc::new(e.into()))
+        self.0.build_non_incentivizing((*min_fee_rate).clone()).map(|e| Arc::new(e.into()))
     }
 }
 
@@ -133,11 +140,8 @@ impl Sender {
     pub fn load(
         token: Arc<SenderToken>,
         persister: Arc<dyn SenderPersister>,
-    ) -> Result<Self, ImplementationError> {
-        Ok(super::Sender::from(
-            (*persister.load(token).map_err(|e| ImplementationError::from(e.to_string()))?).clone(),
-        )
-        .into())
+    ) -> Result<Self, PersistenceError> {
+        Ok(persister.load(token)?.sender())
     }
 
     pub fn extract_v1(&self) -> RequestV1Context {
@@ -173,6 +177,67 @@ impl Sender {
     pub fn key(&self) -> SenderToken {
         self.0.key().into()
     }
+
+    /// When the receiver's session expires, in seconds since the Unix epoch.
+    pub fn expires_at(&self) -> Option<u64> {
+        self.0.expires_at()
+    }
+}
+
+#[cfg(feature = "transport")]
+#[uniffi::export(async_runtime = "tokio")]
+impl Sender {
+    /// Post the Original PSBT to the receiver's directory mailbox.
+    ///
+    /// Returns the context used to poll for the receiver's Payjoin Proposal.
+    pub async fn send(
+        &self,
+        transport: Arc<crate::transport::Transport>,
+    ) -> Result<Arc<V2GetContext>, crate::transport::TransportError> {
+        self.0.send(&transport).await.map(|t| Arc::new(t.into()))
+    }
+}
+
+/// Drives a [`Sender`] from posting the Original PSBT until the Payjoin Proposal arrives,
+/// falling back to BIP 78 when the URI has no v2 endpoint.
+#[cfg(feature = "transport")]
+#[derive(uniffi::Object)]
+pub struct SenderSessionDriver(super::driver::SenderSessionDriver);
+
+#[cfg(feature = "transport")]
+#[uniffi::export(async_runtime = "tokio")]
+impl SenderSessionDriver {
+    #[uniffi::constructor]
+    pub fn new(
+        sender: Arc<Sender>,
+        transport: Arc<crate::transport::Transport>,
+        config: crate::transport::PollConfig,
+        timeout_secs: u64,
+        observer: Option<Arc<dyn super::driver::SenderSessionObserver>>,
+    ) -> Self {
+        Self(super::driver::SenderSessionDriver::new(
+            sender.0.clone(),
+            (*transport).clone(),
+            config,
+            timeout_secs,
+            observer,
+        ))
+    }
+
+    /// Send the Original PSBT and wait for the receiver's Payjoin Proposal.
+    pub async fn run(
+        &self,
+    ) -> Result<super::driver::SenderSessionOutcome, crate::transport::SessionDriverError> {
+        self.0.run().await
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
 }
 
 #[derive(uniffi::Record)]
@@ -208,7 +273,7 @@ impl V1Context {
 }
 
 #[derive(uniffi::Object)]
-pub struct V2PostContext(super::V2PostContext);
+pub struct V2PostContext(Arc<super::V2PostContext>);
 
 #[uniffi::export]
 impl V2PostContext {
@@ -221,10 +286,26 @@ impl V2PostContext {
     ) -> Result<Arc<V2GetContext>, EncapsulationError> {
         self.0.process_response(response).map(|t| Arc::new(t.into()))
     }
+
+    /// Fails if the context was already consumed by `process_response`.
+    pub fn to_json(&self) -> Result<String, ContextSerializationError> {
+        self.0.to_json()
+    }
+
+    #[uniffi::constructor]
+    pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+        super::V2PostContext::from_json(json).map(Into::into)
+    }
 }
 
 impl From<super::V2PostContext> for V2PostContext {
     fn from(value: super::V2PostContext) -> Self {
+        Self(Arc::new(value))
+    }
+}
+
+impl From<Arc<super::V2PostContext>> for V2PostContext {
+    fn from(value: Arc<super::V2PostContext>) -> Self {
         Self(value)
     }
 }
@@ -235,7 +316,7 @@ pub struct RequestOhttpContext {
     pub ohttp_ctx: Arc<crate::ClientResponse>,
 }
 
-#[derive(uniffi::Object)]
+#[derive(Clone, uniffi::Object)]
 pub struct V2GetContext(super::V2GetContext);
 
 impl From<super::V2GetContext> for V2GetContext {
@@ -265,15 +346,46 @@ impl V2GetContext {
     ) -> Result<Option<String>, ResponseError> {
         self.0.process_response(response, ohttp_ctx.as_ref())
     }
+
+    pub fn to_json(&self) -> Result<String, SerdeJsonError> {
+        self.0.to_json()
+    }
+
+    #[uniffi::constructor]
+    pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+        super::V2GetContext::from_json(json).map(Into::into)
+    }
+}
+
+#[cfg(feature = "transport")]
+#[uniffi::export(async_runtime = "tokio")]
+impl V2GetContext {
+    /// Poll the directory once for the receiver's Payjoin Proposal PSBT.
+    ///
+    /// Returns `None` if the receiver has not responded yet.
+    pub async fn poll(
+        &self,
+        transport: Arc<crate::transport::Transport>,
+    ) -> Result<Option<String>, crate::transport::TransportError> {
+        self.0.poll(&transport).await
+    }
 }
 
 #[uniffi::export(with_foreign)]
 pub trait SenderPersister: Send + Sync {
-    fn save(&self, sender: Arc<Sender>) -> Result<Arc<SenderToken>, ForeignError>;
-    fn load(&self, token: Arc<SenderToken>) -> Result<Arc<Sender>, ForeignError>;
+    /// Store `session`, replacing any session saved under the same token.
+    fn save(&self, session: Arc<SenderSession>) -> Result<Arc<SenderToken>, PersistenceError>;
+    fn load(&self, token: Arc<SenderToken>) -> Result<Arc<SenderSession>, PersistenceError>;
+    /// Tokens of every stored sender session.
+    fn list(&self) -> Result<Vec<Arc<SenderToken>>, PersistenceError>;
+    /// Remove the sender session stored under `token`, if any.
+    fn delete(&self, token: Arc<SenderToken>) -> Result<(), PersistenceError>;
+    fn metadata(&self, token: Arc<SenderToken>) -> Result<SessionMetadata, PersistenceError>;
 }
 
 // The adapter to use the save and load callbacks
+//
+// A plain Sender is stored as the start of its session.
 struct CallbackPersisterAdapter {
     callback_persister: Arc<dyn SenderPersister>,
 }
@@ -284,19 +396,245 @@ impl CallbackPersisterAdapter {
     }
 }
 
+impl payjoin::persist::Persister<super::SenderSession> for CallbackPersisterAdapter {
+    type Token = SenderToken;
+    type Error = PersistenceError;
+
+    fn save(&mut self, session: super::SenderSession) -> Result<Self::Token, Self::Error> {
+        let res = self.callback_persister.save(Arc::new(session.into()))?;
+        Ok((*res).clone())
+    }
+
+    fn load(&self, token: Self::Token) -> Result<super::SenderSession, Self::Error> {
+        self.callback_persister.load(token.into()).map(|session| session.as_ref().into())
+    }
+}
+
 // Implement the Persister trait for the adapter
 impl payjoin::persist::Persister<payjoin::send::v2::Sender> for CallbackPersisterAdapter {
     type Token = SenderToken; // Define the token type
-    type Error = ForeignError; // Define the error type
+    type Error = PersistenceError; // Define the error type
 
     fn save(&mut self, sender: payjoin::send::v2::Sender) -> Result<Self::Token, Self::Error> {
-        let sender = Sender(super::Sender::from(sender));
-        self.callback_persister.save(sender.into()).map(|token| (*token).clone())
+        let session = super::SenderSession::new(sender.into());
+        payjoin::persist::Persister::<super::SenderSession>::save(self, session)
     }
 
     fn load(&self, token: Self::Token) -> Result<payjoin::send::v2::Sender, Self::Error> {
-        // Use the callback to load the sender
-        self.callback_persister.load(token.into()).map(|sender| (*sender).clone().0 .0)
+        let session = payjoin::persist::Persister::<super::SenderSession>::load(self, token)?;
+        Ok(session.sender().into())
+    }
+}
+
+/// Where a [`SenderSession`] is in the BIP 77 flow.
+#[derive(uniffi::Enum)]
+pub enum SenderSessionState {
+    /// The Original PSBT has not been sent yet.
+    Initialized,
+    /// The Original PSBT request was extracted and the directory's response has not been
+    /// processed yet.
+    ///
+    /// Resend the request or process its response with the context.
+    PostingOriginalPsbt { context: Arc<V2PostContext> },
+    /// The Original PSBT was posted and the receiver has not replied yet.
+    ///
+    /// Resume polling with the context.
+    AwaitingProposal { context: Arc<V2GetContext> },
+    /// The receiver replied with a Payjoin Proposal PSBT.
+    ProposalReceived { psbt: String },
+}
+
+impl From<super::SenderSessionState> for SenderSessionState {
+    fn from(value: super::SenderSessionState) -> Self {
+        use super::SenderSessionState as State;
+        match value {
+            State::Initialized => Self::Initialized,
+            State::PostingOriginalPsbt(ctx) => {
+                Self::PostingOriginalPsbt { context: Arc::new(ctx.into()) }
+            }
+            State::AwaitingProposal(ctx) => {
+                Self::AwaitingProposal { context: Arc::new(ctx.into()) }
+            }
+            State::ProposalReceived { psbt } => Self::ProposalReceived { psbt },
+        }
+    }
+}
+
+/// Something that happened in a [`SenderSession`].
+///
+/// Replaying the history reconstructs the current [`SenderSessionState`].
+#[derive(uniffi::Enum)]
+pub enum SenderSessionEvent {
+    Created,
+    /// The Original PSBT request was extracted, holding the same [`V2PostContext`] returned to
+    /// the caller.
+    OriginalPsbtSent {
+        context: Arc<V2PostContext>,
+    },
+    /// The Original PSBT was posted to the receiver's directory mailbox.
+    OriginalPsbtPosted {
+        context: Arc<V2GetContext>,
+    },
+    /// The receiver has no v2 endpoint and the Original PSBT was sent over BIP 78.
+    FellBackToV1,
+    /// The directory was polled for the Payjoin Proposal.
+    Polled,
+    ProposalReceived {
+        psbt: String,
+    },
+    /// The receiver's response failed validation.
+    ResponseRejected {
+        error: String,
+    },
+}
+
+impl From<super::SenderSessionEvent> for SenderSessionEvent {
+    fn from(value: super::SenderSessionEvent) -> Self {
+        use super::SenderSessionEvent as Event;
+        match value {
+            Event::Created => Self::Created,
+            Event::OriginalPsbtSent(ctx) => {
+                Self::OriginalPsbtSent { context: Arc::new(ctx.into()) }
+            }
+            Event::OriginalPsbtPosted(ctx) => {
+                Self::OriginalPsbtPosted { context: Arc::new(ctx.into()) }
+            }
+            Event::FellBackToV1 => Self::FellBackToV1,
+            Event::Polled => Self::Polled,
+            Event::ProposalReceived { psbt } => Self::ProposalReceived { psbt },
+            Event::ResponseRejected { error } => Self::ResponseRejected { error },
+        }
+    }
+}
+
+/// A [`Sender`] together with the append-only history of its session.
+///
+/// Advance the session through its own methods, which record each step, and save it after
+/// every step so it can be resumed exactly where it left off.
+#[derive(uniffi::Object)]
+pub struct SenderSession(Mutex<super::SenderSession>);
+
+impl From<super::SenderSession> for SenderSession {
+    fn from(value: super::SenderSession) -> Self {
+        Self(Mutex::new(value))
+    }
+}
+
+impl From<&SenderSession> for super::SenderSession {
+    fn from(value: &SenderSession) -> Self {
+        value.session().clone()
+    }
+}
+
+impl From<super::SenderSession> for SenderToken {
+    fn from(value: super::SenderSession) -> Self {
+        SenderToken(value.into())
+    }
+}
+
+impl SenderSession {
+    fn session(&self) -> MutexGuard<'_, super::SenderSession> {
+        // The session is only ever replaced whole, so a poisoned lock still holds a valid one
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[uniffi::export]
+impl SenderSession {
+    /// Start tracking a session whose Original PSBT has not been sent yet.
+    #[uniffi::constructor]
+    pub fn new(sender: Arc<Sender>) -> Self {
+        super::SenderSession::new((*sender).clone().into()).into()
+    }
+
+    /// Loads a [`SenderSession`] from the provided persister using the storage token.
+    #[uniffi::constructor]
+    pub fn load(
+        token: Arc<SenderToken>,
+        persister: Arc<dyn SenderPersister>,
+    ) -> Result<Self, PersistenceError> {
+        Ok(super::SenderSession::from(persister.load(token)?.as_ref()).into())
+    }
+
+    /// Saves the session using the provided persister and returns the storage token.
+    pub fn persist(
+        &self,
+        persister: Arc<dyn SenderPersister>,
+    ) -> Result<SenderToken, ImplementationError> {
+        let mut adapter = CallbackPersisterAdapter::new(persister);
+        self.session().persist(&mut adapter)
+    }
+
+    pub fn sender(&self) -> Sender {
+        self.session().sender().into()
+    }
+
+    pub fn state(&self) -> SenderSessionState {
+        self.session().state().into()
+    }
+
+    pub fn events(&self) -> Vec<SenderSessionEvent> {
+        self.session().events().into_iter().map(Into::into).collect()
+    }
+
+    /// The session history with timestamps, for display or logging.
+    pub fn history(&self) -> Vec<SessionEventRecord> {
+        self.session().history()
+    }
+
+    /// Extract the request posting the Original PSBT, see [`Sender::extract_v2`].
+    ///
+    /// The session records the returned context itself, so it is still single use. Save the
+    /// session before posting the request to be able to process the response after a restart.
+    pub fn extract_v2(
+        &self,
+        ohttp_relay: Arc<Url>,
+    ) -> Result<RequestV2PostContext, CreateRequestError> {
+        let (request, ctx) = self.session().extract_v2((*ohttp_relay).clone())?;
+        Ok(RequestV2PostContext { request, context: Arc::new(ctx.into()) })
+    }
+
+    /// Process the directory's response to posting the Original PSBT, see
+    /// [`V2PostContext::process_response`].
+    pub fn process_post_response(
+        &self,
+        context: Arc<V2PostContext>,
+        response: &[u8],
+    ) -> Result<Arc<V2GetContext>, EncapsulationError> {
+        self.session().process_post_response(&context.0, response).map(|t| Arc::new(t.into()))
+    }
+
+    /// Process the directory's response to a poll for the Payjoin Proposal, see
+    /// [`V2GetContext::process_response`].
+    pub fn process_get_response(
+        &self,
+        context: Arc<V2GetContext>,
+        response: &[u8],
+        ohttp_ctx: Arc<ClientResponse>,
+    ) -> Result<Option<String>, ResponseError> {
+        self.session().process_get_response(&context.0, response, &ohttp_ctx)
+    }
+
+    /// Process the receiver's BIP 78 response, see [`V1Context::process_response`].
+    pub fn process_v1_response(
+        &self,
+        context: Arc<V1Context>,
+        response: Vec<u8>,
+    ) -> Result<String, ResponseError> {
+        self.session().process_v1_response(&context.0, response)
+    }
+
+    pub fn to_json(&self) -> Result<String, SerdeJsonError> {
+        self.session().to_json()
+    }
+
+    #[uniffi::constructor]
+    pub fn from_json(json: &str) -> Result<Self, SerdeJsonError> {
+        super::SenderSession::from_json(json).map(Into::into)
+    }
+
+    pub fn key(&self) -> SenderToken {
+        self.session().key().into()
     }
 }
 
@@ -321,3 +659,9 @@ impl From<payjoin::send::v2::SenderToken> for SenderToken {
         SenderToken(value)
     }
 }
+
+impl From<SenderToken> for payjoin::send::v2::SenderToken {
+    fn from(value: SenderToken) -> Self {
+        value.0
+    }
+}
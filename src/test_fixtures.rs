@@ -0,0 +1,115 @@
+//! Fixtures shared by the unit tests.
+
+use std::collections::HashMap;
+
+use payjoin::persist::{NoopPersister, Persister, Value};
+use payjoin::receive::v2::ReceiverToken;
+use payjoin::send::v2::SenderToken;
+
+use crate::bitcoin_ffi::{Address, FeeRate, Network};
+use crate::error::PersistenceError;
+use crate::receive::{NewReceiver, Receiver};
+use crate::send::{NewSender, Sender, SenderBuilder};
+use crate::{OhttpKeys, PjUri, Uri};
+
+/// A testnet address for receivers that are not paid by [`ORIGINAL_PSBT`].
+pub(crate) const RECEIVER_ADDRESS: &str = "tb1q6d3a2w975yny0asuvd9a67ner4nks58ff0q8g4";
+
+pub(crate) const OHTTP_KEYS: &str = "OH1QYPM5JXYNS754Y4R45QWE336QFX6ZR8DQGVQCULVZTV20TFVEYDMFQC";
+
+/// The address [`ORIGINAL_PSBT`] pays.
+pub(crate) const PAYEE_ADDRESS: &str = "2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK";
+
+/// A v1 payjoin URI paying [`PAYEE_ADDRESS`].
+pub(crate) const PAYEE_URI: &str =
+    "bitcoin:2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK?pj=https://example.com/pj";
+
+/// The Original PSBT from the BIP 78 test vectors.
+pub(crate) const ORIGINAL_PSBT: &str = "cHNidP8BAHMCAAAAAY8nutGgJdyYGXWiBEb45Hoe9lWGbkxh/6bNiOJdCDuDAAAAAAD+////AtyVuAUAAAAAF6kUHehJ8GnSdBUOOv6ujXLrWmsJRDCHgIQeAAAAAAAXqRR3QJbbz0hnQ8IvQ0fptGn+votneofTAAAAAAEBIKgb1wUAAAAAF6kU3k4ekGHKWRNbA1rV5tR5kEVDVNCHAQcXFgAUx4pFclNVgo1WWAdN1SYNX8tphTABCGsCRzBEAiB8Q+A6dep+Rz92vhy26lT0AjZn4PRLi8Bf9qoB/CMk0wIgP/Rj2PWZ3gEjUkTlhDRNAQ0gXwTO7t9n+V14pZ6oljUBIQMVmsAaoNWHVMS02LfTSe0e388LNitPa1UQZyOihY+FFgABABYAFEb2Giu6c4KO5YW0pfw3lGp9jMUUAAA=";
+
+/// A receiver for `address` using the `https://example.com` directory.
+pub(crate) fn new_receiver_for(address: &str, expire_after: Option<u64>) -> NewReceiver {
+    let address = Address::new(address.to_string(), Network::Testnet).unwrap();
+    let ohttp_keys = OhttpKeys::from_string(OHTTP_KEYS.to_string()).unwrap();
+    NewReceiver::new(address, "https://example.com".to_string(), ohttp_keys, expire_after).unwrap()
+}
+
+pub(crate) fn new_receiver(expire_after: Option<u64>) -> NewReceiver {
+    new_receiver_for(RECEIVER_ADDRESS, expire_after)
+}
+
+pub(crate) fn receiver() -> Receiver {
+    load_receiver(new_receiver(None))
+}
+
+pub(crate) fn load_receiver(new_receiver: NewReceiver) -> Receiver {
+    let token = new_receiver.persist(&mut NoopPersister).unwrap();
+    Receiver::load(token, &NoopPersister).unwrap()
+}
+
+/// A sender of [`ORIGINAL_PSBT`] to [`PAYEE_URI`].
+pub(crate) fn new_sender() -> NewSender {
+    new_sender_to(payee_uri())
+}
+
+/// A sender of [`ORIGINAL_PSBT`] to `uri`.
+pub(crate) fn new_sender_to(uri: PjUri) -> NewSender {
+    SenderBuilder::new(ORIGINAL_PSBT.to_string(), uri)
+        .unwrap()
+        .build_recommended(FeeRate::from_sat_per_kwu(1000))
+        .unwrap()
+}
+
+pub(crate) fn load_sender(new_sender: NewSender) -> Sender {
+    let token = new_sender.persist(&mut NoopPersister).unwrap();
+    Sender::load(token, &NoopPersister).unwrap()
+}
+
+pub(crate) fn payee_uri() -> PjUri {
+    Uri::parse(PAYEE_URI.to_string()).unwrap().check_pj_supported().unwrap()
+}
+
+/// A sender to a v2 receiver for [`PAYEE_ADDRESS`].
+pub(crate) fn v2_sender() -> Sender {
+    let uri = load_receiver(new_receiver_for(PAYEE_ADDRESS, None)).pj_uri();
+    load_sender(new_sender_to(uri))
+}
+
+/// Stores receivers and senders as JSON, keyed by their token.
+#[derive(Default)]
+pub(crate) struct InMemoryPersister {
+    pub(crate) values: HashMap<String, String>,
+    /// Fail every load as if the backend were down.
+    pub(crate) unavailable: bool,
+}
+
+macro_rules! impl_in_memory_persister {
+    ($value:ty, $token:ty) => {
+        impl Persister<$value> for InMemoryPersister {
+            type Token = $token;
+            type Error = PersistenceError;
+
+            fn save(&mut self, value: $value) -> Result<Self::Token, Self::Error> {
+                let token = value.key();
+                let json = serde_json::to_string(&value)
+                    .map_err(|e| PersistenceError::Backend { msg: e.to_string() })?;
+                self.values.insert(token.to_string(), json);
+                Ok(token)
+            }
+
+            fn load(&self, token: Self::Token) -> Result<$value, Self::Error> {
+                if self.unavailable {
+                    return Err(PersistenceError::Backend { msg: "unavailable".to_string() });
+                }
+                let json = self
+                    .values
+                    .get(&token.to_string())
+                    .ok_or(PersistenceError::NotFound { token: token.to_string() })?;
+                Ok(serde_json::from_str(json)?)
+            }
+        }
+    };
+}
+
+impl_in_memory_persister!(payjoin::receive::v2::Receiver, ReceiverToken);
+impl_in_memory_persister!(payjoin::send::v2::Sender, SenderToken);
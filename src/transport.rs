@@ -0,0 +1,262 @@
+//! Optional HTTP transport that performs OHTTP round-trips on behalf of the caller.
+//!
+//! Without this feature every [`Request`] must be sent by host-language HTTP code and the
+//! response bytes fed back into the matching `process_*` method. [`Transport`] does that
+//! round-trip in Rust, and the `send`/`poll` helpers below chain it with the typestates.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+use crate::receive::{PayjoinProposal, Receiver, UncheckedProposal};
+use crate::send::{CreateRequestError, EncapsulationError, ResponseError, Sender, V2GetContext};
+use crate::{Request, Url};
+
+/// Settings for the HTTP client backing a [`Transport`].
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct TransportConfig {
+    /// Overall timeout for each request, in seconds. No timeout is applied when `None`.
+    pub timeout_secs: Option<u64>,
+    /// Proxy URL every request is routed through, e.g. `socks5h://127.0.0.1:9050`.
+    pub proxy: Option<String>,
+    /// Additional DER-encoded root certificates to trust.
+    pub root_certificates: Vec<Vec<u8>>,
+}
+
+/// Error that may occur while driving a request through a [`Transport`].
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
+pub enum TransportError {
+    /// The HTTP client could not be built from the [`TransportConfig`].
+    #[error("Invalid transport configuration: {msg}")]
+    Config { msg: String },
+    /// The request could not be sent or its response could not be read.
+    #[error("HTTP request failed: {msg}")]
+    Http { msg: String },
+    /// The OHTTP relay replied with a non-success status code.
+    #[error("Unexpected HTTP status code: {status}")]
+    UnexpectedStatusCode { status: u16 },
+    /// The receiver state machine rejected the request or response.
+    #[error("Receiver error: {0}")]
+    Receiver(#[from] crate::receive::Error),
+    /// The sender request could not be created.
+    #[error("Error creating the sender request: {0}")]
+    CreateRequest(Arc<CreateRequestError>),
+    /// The sender could not decapsulate the directory response.
+    #[error("Error decapsulating the sender response: {0}")]
+    Encapsulation(Arc<EncapsulationError>),
+    /// The receiver response was rejected by the sender.
+    #[error("Error validating the receiver response: {0}")]
+    Response(#[from] ResponseError),
+}
+
+impl From<reqwest::Error> for TransportError {
+    fn from(value: reqwest::Error) -> Self {
+        TransportError::Http { msg: value.to_string() }
+    }
+}
+
+impl From<CreateRequestError> for TransportError {
+    fn from(value: CreateRequestError) -> Self {
+        TransportError::CreateRequest(Arc::new(value))
+    }
+}
+
+impl From<EncapsulationError> for TransportError {
+    fn from(value: EncapsulationError) -> Self {
+        TransportError::Encapsulation(Arc::new(value))
+    }
+}
+
+/// Backoff settings for the session drivers.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Record))]
+pub struct PollConfig {
+    /// Delay after the first empty or failed poll, in milliseconds.
+    pub initial_backoff_ms: u64,
+    /// Upper bound on the delay between polls, in milliseconds.
+    pub max_backoff_ms: u64,
+}
+
+impl Default for PollConfig {
+    fn default() -> Self {
+        Self { initial_backoff_ms: 1_000, max_backoff_ms: 30_000 }
+    }
+}
+
+/// Error that ends a session driver's run.
+#[derive(Debug, thiserror::Error)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
+pub enum SessionDriverError {
+    /// The driver was cancelled before the session completed.
+    #[error("Session driver was cancelled")]
+    Cancelled,
+    /// The session expired before the counterparty responded.
+    #[error("Session expired")]
+    Expired,
+    /// A request failed in a way that retrying cannot fix.
+    #[error("Transport error: {0}")]
+    Transport(#[from] TransportError),
+}
+
+/// Cancellation flag shared between a running driver and the caller.
+#[derive(Debug, Default)]
+pub(crate) struct Cancellation {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl Cancellation {
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Run `fut` to completion unless the driver is cancelled first.
+    pub(crate) async fn run<F: Future>(&self, fut: F) -> Result<F::Output, SessionDriverError> {
+        let notified = self.notify.notified();
+        if self.is_cancelled() {
+            return Err(SessionDriverError::Cancelled);
+        }
+        tokio::select! {
+            output = fut => Ok(output),
+            _ = notified => Err(SessionDriverError::Cancelled),
+        }
+    }
+
+    /// Sleep for `duration` unless the driver is cancelled first.
+    pub(crate) async fn sleep(&self, duration: Duration) -> Result<(), SessionDriverError> {
+        self.run(tokio::time::sleep(duration)).await
+    }
+}
+
+impl TransportError {
+    /// Whether the failure is a network hiccup worth retrying rather than a protocol error.
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            TransportError::Http { .. } => true,
+            TransportError::UnexpectedStatusCode { status } => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
+}
+
+/// An HTTP client bound to an OHTTP relay.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct Transport {
+    client: reqwest::Client,
+    ohttp_relay: Url,
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export(async_runtime = "tokio"))]
+impl Transport {
+    /// Build a transport that routes v2 requests through `ohttp_relay`.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn new(ohttp_relay: Arc<Url>, config: TransportConfig) -> Result<Self, TransportError> {
+        let mut builder = reqwest::Client::builder().use_rustls_tls();
+        if let Some(timeout) = config.timeout_secs {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+        if let Some(proxy) = config.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| TransportError::Config { msg: e.to_string() })?;
+            builder = builder.proxy(proxy);
+        }
+        for der in config.root_certificates {
+            let cert = reqwest::Certificate::from_der(&der)
+                .map_err(|e| TransportError::Config { msg: e.to_string() })?;
+            builder = builder.add_root_certificate(cert);
+        }
+        let client = builder.build().map_err(|e| TransportError::Config { msg: e.to_string() })?;
+        Ok(Self { client, ohttp_relay: (*ohttp_relay).clone() })
+    }
+
+    /// The OHTTP relay this transport sends v2 requests through.
+    pub fn ohttp_relay(&self) -> Url {
+        self.ohttp_relay.clone()
+    }
+
+    /// POST `request` and return the response body.
+    pub async fn post(&self, request: Request) -> Result<Vec<u8>, TransportError> {
+        let response = self.send(request).await?;
+        if !response.status().is_success() {
+            return Err(TransportError::UnexpectedStatusCode {
+                status: response.status().as_u16(),
+            });
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+impl Transport {
+    /// POST a BIP 78 `request` and return the response body whatever its status code.
+    ///
+    /// BIP 78 receivers reply with a JSON error body and a non-success status code.
+    pub(crate) async fn post_v1(&self, request: Request) -> Result<Vec<u8>, TransportError> {
+        Ok(self.send(request).await?.bytes().await?.to_vec())
+    }
+
+    async fn send(&self, request: Request) -> Result<reqwest::Response, TransportError> {
+        Ok(self
+            .client
+            .post(request.url.as_string())
+            .header("Content-Type", request.content_type)
+            .body(request.body)
+            .send()
+            .await?)
+    }
+}
+
+impl Receiver {
+    /// Poll the directory once for a proposal from the sender.
+    ///
+    /// Returns `None` if the sender has not posted an Original PSBT yet.
+    pub async fn poll(
+        &self,
+        transport: &Transport,
+    ) -> Result<Option<UncheckedProposal>, TransportError> {
+        let (req, ctx) = self.extract_req(transport.ohttp_relay.as_string())?;
+        let res = transport.post(req).await?;
+        Ok(self.process_res(&res, &ctx)?)
+    }
+}
+
+impl PayjoinProposal {
+    /// Post the Payjoin Proposal PSBT to the directory for the sender to pick up.
+    pub async fn send(&self, transport: &Transport) -> Result<(), TransportError> {
+        let (req, ctx) = self.extract_req(transport.ohttp_relay.as_string())?;
+        let res = transport.post(req).await?;
+        Ok(self.process_res(&res, &ctx)?)
+    }
+}
+
+impl Sender {
+    /// Post the Original PSBT to the receiver's directory mailbox.
+    ///
+    /// Returns the context used to poll for the receiver's Payjoin Proposal.
+    pub async fn send(&self, transport: &Transport) -> Result<V2GetContext, TransportError> {
+        let (req, ctx) = self.extract_v2(transport.ohttp_relay.clone())?;
+        let res = transport.post(req).await?;
+        Ok(ctx.process_response(&res)?)
+    }
+}
+
+impl V2GetContext {
+    /// Poll the directory once for the receiver's Payjoin Proposal PSBT.
+    ///
+    /// Returns `None` if the receiver has not responded yet.
+    pub async fn poll(&self, transport: &Transport) -> Result<Option<String>, TransportError> {
+        let (req, ctx) = self.extract_req(transport.ohttp_relay.as_string())?;
+        let res = transport.post(req).await?;
+        Ok(self.process_response(&res, &ctx)?)
+    }
+}
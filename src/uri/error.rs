@@ -1,27 +1,100 @@
+use std::str::FromStr;
+
+use payjoin::bitcoin::address::NetworkUnchecked;
+
+use crate::bitcoin_ffi::Network;
+
+/// The error `payjoin::Uri` returns from `from_str`.
+type UriParseError = <payjoin::Uri<'static, NetworkUnchecked> as FromStr>::Err;
+
+/// Why a string could not be parsed as a BIP 21 URI with payjoin parameters.
 #[derive(Debug, PartialEq, Eq, thiserror::Error)]
-#[error("Error parsing the payjoin URI: {msg}")]
-#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
-pub struct PjParseError {
-    msg: String,
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
+pub enum PjParseError {
+    /// The URI does not start with `bitcoin:`.
+    #[error("The URI scheme is not bitcoin:")]
+    InvalidScheme,
+    #[error("The URI address is invalid")]
+    InvalidAddress,
+    /// The URI address belongs to a different network than the one required.
+    #[error("The URI address {address} is not valid for {network}")]
+    WrongNetwork { address: String, network: Network },
+    #[error("The URI amount is invalid")]
+    InvalidAmount,
+    /// A `req-` parameter this library does not understand.
+    #[error("The URI contains the unknown required parameter {param}")]
+    UnknownRequiredParameter { param: String },
+    #[error("The URI parameter {param} appears more than once")]
+    DuplicateParameter { param: String },
+    /// The `pjos` parameter is not `0` or `1`.
+    #[error("The pjos parameter is invalid")]
+    InvalidOutputSubstitution,
+    /// Payjoin parameters are present without a `pj` endpoint.
+    #[error("The payjoin endpoint is missing")]
+    MissingEndpoint,
+    /// The `pj` endpoint is not a valid URL.
+    #[error("The payjoin endpoint is invalid: {msg}")]
+    InvalidEndpoint { msg: String },
+    /// The `pj` endpoint is neither https nor an onion service.
+    #[error("The payjoin endpoint is not secure")]
+    InsecureEndpoint,
+    /// An error this version of the bindings does not recognize.
+    #[error("Error parsing the payjoin URI: {msg}")]
+    Unexpected { msg: String },
 }
 
-impl From<String> for PjParseError {
-    fn from(msg: String) -> Self {
-        PjParseError { msg }
+impl From<UriParseError> for PjParseError {
+    fn from(value: UriParseError) -> Self {
+        // The upstream variants are private, so they are told apart by their messages
+        let msg = value.to_string();
+        let quoted = |prefix: &str| {
+            let (_, rest) = msg.split_once(prefix)?;
+            Some(rest.trim_matches(|c| c == '\'' || c == '"').to_string())
+        };
+        if let Some(param) = quoted("unknown required parameter ") {
+            return PjParseError::UnknownRequiredParameter { param };
+        }
+        if let Some(param) = quoted("Multiple instances of parameter ") {
+            return PjParseError::DuplicateParameter { param };
+        }
+        if msg.contains("Endpoint scheme is not secure") {
+            return PjParseError::InsecureEndpoint;
+        }
+        if msg.contains("Endpoint is not valid") {
+            return PjParseError::InvalidEndpoint { msg };
+        }
+        if msg.contains("invalid scheme") {
+            PjParseError::InvalidScheme
+        } else if msg.contains("address is invalid") {
+            PjParseError::InvalidAddress
+        } else if msg.contains("amount is invalid") {
+            PjParseError::InvalidAmount
+        } else if msg.contains("Bad pjos parameter") {
+            PjParseError::InvalidOutputSubstitution
+        } else if msg.contains("Missing payjoin endpoint") {
+            PjParseError::MissingEndpoint
+        } else {
+            PjParseError::Unexpected { msg }
+        }
     }
 }
 
+/// Why a BIP 21 URI cannot be used for payjoin.
 #[derive(Debug, PartialEq, Eq, thiserror::Error)]
-#[error("URI doesn't support payjoin: {msg}")]
-#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
-pub struct PjNotSupported {
-    msg: String,
-}
-
-impl From<String> for PjNotSupported {
-    fn from(msg: String) -> Self {
-        PjNotSupported { msg }
-    }
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error))]
+pub enum PjNotSupported {
+    /// The URI has no `pj` parameter.
+    #[error("The URI has no payjoin endpoint")]
+    MissingEndpoint,
+    /// The v2 endpoint fragment lacks the receiver's public key (`RK1`).
+    #[error("The payjoin endpoint is missing the receiver key")]
+    MissingReceiverKey,
+    /// The v2 endpoint fragment lacks the OHTTP keys (`OH1`).
+    #[error("The payjoin endpoint is missing the OHTTP keys")]
+    MissingOhttpKeys,
+    /// The v2 endpoint fragment lacks the session expiration (`EX1`).
+    #[error("The payjoin endpoint is missing the expiration")]
+    MissingExpiration,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -1,14 +1,21 @@
+use std::collections::HashMap;
 use std::str::FromStr;
-#[cfg(feature = "uniffi")]
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub use error::{PjNotSupported, PjParseError, UrlParseError};
 use payjoin::bitcoin::address::NetworkChecked;
+use payjoin::bitcoin::bech32::primitives::decode::CheckedHrpstring;
+use payjoin::bitcoin::bech32::{Bech32, Bech32m, NoChecksum};
 use payjoin::UriExt;
 
+use crate::bitcoin_ffi::{Address, Amount, Network};
+use crate::{OhttpKeys, OutputSubstitution};
+
 pub mod error;
 #[derive(Clone)]
-pub struct Uri(payjoin::Uri<'static, NetworkChecked>);
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct Uri(payjoin::Uri<'static, NetworkChecked>, ExtraParams);
 impl From<Uri> for payjoin::Uri<'static, NetworkChecked> {
     fn from(value: Uri) -> Self {
         value.0
@@ -17,23 +24,44 @@ impl From<Uri> for payjoin::Uri<'static, NetworkChecked> {
 
 impl From<payjoin::Uri<'static, NetworkChecked>> for Uri {
     fn from(value: payjoin::Uri<'static, NetworkChecked>) -> Self {
-        Uri(value)
+        Uri(value, ExtraParams::default())
     }
 }
 
+#[cfg_attr(feature = "uniffi", uniffi::export)]
 impl Uri {
+    /// Parse a URI for any network.
+    ///
+    /// Wallets should prefer [`Uri::parse_for_network`] so a URI for another network is
+    /// rejected.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
     pub fn parse(uri: String) -> Result<Self, PjParseError> {
-        match payjoin::Uri::from_str(uri.as_str()) {
-            Ok(e) => Ok(e.assume_checked().into()),
-            Err(e) => Err(e.to_string().into()),
-        }
+        let uri = lowercase_scheme(uri);
+        let extras = ExtraParams::parse(&uri);
+        Ok(Uri(payjoin::Uri::from_str(uri.as_str())?.assume_checked(), extras))
+    }
+
+    /// Parse a URI whose address must be valid for `network`.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn parse_for_network(uri: String, network: Network) -> Result<Self, PjParseError> {
+        let uri = lowercase_scheme(uri);
+        let extras = ExtraParams::parse(&uri);
+        let uri = payjoin::Uri::from_str(uri.as_str())?;
+        check_network(&uri.address, network)?;
+        Ok(Uri(uri.assume_checked(), extras))
+    }
+
+    /// Returns this URI if its address is valid for `network`.
+    pub fn require_network(&self, network: Network) -> Result<Self, PjParseError> {
+        check_network(self.0.address.as_unchecked(), network)?;
+        Ok(self.clone())
     }
     pub fn address(&self) -> String {
         self.clone().0.address.to_string()
     }
-    /// Gets the amount in satoshis.
-    pub fn amount_sats(&self) -> Option<u64> {
-        self.0.amount.map(|x| x.to_sat())
+    /// The requested payment amount.
+    pub fn amount(&self) -> Option<Arc<Amount>> {
+        self.0.amount.map(|amount| Arc::new(amount.into()))
     }
     pub fn label(&self) -> Option<String> {
         self.0.label.clone().and_then(|x| String::try_from(x).ok())
@@ -43,26 +71,92 @@ impl Uri {
     }
     #[cfg(not(feature = "uniffi"))]
     pub fn check_pj_supported(&self) -> Result<PjUri, PjNotSupported> {
-        match self.0.clone().check_pj_supported() {
-            Ok(e) => Ok(e.into()),
-            Err(uri) => Err(uri.to_string().into()),
-        }
+        self.pj_uri()
     }
     #[cfg(feature = "uniffi")]
     pub fn check_pj_supported(&self) -> Result<Arc<PjUri>, PjNotSupported> {
-        match self.0.clone().check_pj_supported() {
-            Ok(e) => Ok(Arc::new(e.into())),
-            Err(uri) => Err(uri.to_string().into()),
-        }
+        self.pj_uri().map(Arc::new)
+    }
+    /// Query parameters that are not part of BIP 21 or payjoin, e.g. `lightning`.
+    ///
+    /// BIP 21 rejects URIs with unknown `req-` parameters, so none are included here.
+    pub fn extras(&self) -> HashMap<String, String> {
+        self.1.to_map()
+    }
+    /// A BOLT11 invoice from the `lightning` parameter.
+    pub fn lightning(&self) -> Option<String> {
+        self.1.get(LIGHTNING)
+    }
+    /// A BOLT12 offer from the `lno` parameter.
+    pub fn lno(&self) -> Option<String> {
+        self.1.get(LNO)
+    }
+    /// A silent payment address from the `sp` parameter.
+    pub fn sp(&self) -> Option<String> {
+        self.1.get(SP)
     }
     pub fn as_string(&self) -> String {
-        self.0.clone().to_string()
+        self.1.append_to(self.0.clone().to_string())
+    }
+}
+
+impl Uri {
+    fn pj_uri(&self) -> Result<PjUri, PjNotSupported> {
+        let uri =
+            self.0.clone().check_pj_supported().map_err(|_| PjNotSupported::MissingEndpoint)?;
+        check_v2_endpoint(uri.extras.endpoint())?;
+        Ok(PjUri(uri, self.1.clone()))
+    }
+}
+
+/// The scheme is case-insensitive, and QR codes often carry it uppercased.
+fn lowercase_scheme(uri: String) -> String {
+    match uri.get(..8) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("bitcoin:") => {
+            format!("bitcoin:{}", &uri[8..])
+        }
+        _ => uri,
+    }
+}
+
+fn check_network(
+    address: &payjoin::bitcoin::Address<payjoin::bitcoin::address::NetworkUnchecked>,
+    network: Network,
+) -> Result<(), PjParseError> {
+    if address.is_valid_for_network(network.into()) {
+        return Ok(());
+    }
+    Err(PjParseError::WrongNetwork {
+        address: address.clone().assume_checked().to_string(),
+        network,
+    })
+}
+
+/// Whether the endpoint carries any v2 fragment parameter. Endpoints without them are v1
+/// endpoints.
+pub(crate) fn is_v2_endpoint(endpoint: &payjoin::Url) -> bool {
+    ["RK", "OH", "EX"].iter().any(|hrp| fragment_param(endpoint, hrp).is_some())
+}
+
+/// A v2 endpoint must carry every fragment parameter a sender needs.
+fn check_v2_endpoint(endpoint: &payjoin::Url) -> Result<(), PjNotSupported> {
+    let params = [
+        ("RK", PjNotSupported::MissingReceiverKey),
+        ("OH", PjNotSupported::MissingOhttpKeys),
+        ("EX", PjNotSupported::MissingExpiration),
+    ];
+    if !is_v2_endpoint(endpoint) {
+        return Ok(());
+    }
+    match params.into_iter().find(|(hrp, _)| fragment_param(endpoint, hrp).is_none()) {
+        Some((_, missing)) => Err(missing),
+        None => Ok(()),
     }
 }
 
 impl From<payjoin::PjUri<'static>> for PjUri {
     fn from(value: payjoin::PjUri<'static>) -> Self {
-        Self(value)
+        Self(value, ExtraParams::default())
     }
 }
 
@@ -74,35 +168,416 @@ impl<'a> From<PjUri> for payjoin::PjUri<'a> {
 
 #[derive(Clone)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
-pub struct PjUri(pub payjoin::PjUri<'static>);
+pub struct PjUri(pub payjoin::PjUri<'static>, ExtraParams);
 
 #[cfg_attr(feature = "uniffi", uniffi::export)]
 impl PjUri {
     pub fn address(&self) -> String {
         self.0.clone().address.to_string()
     }
-    /// Number of sats requested as payment
-    pub fn amount_sats(&self) -> Option<u64> {
-        self.0.clone().amount.map(|e| e.to_sat())
+    /// The requested payment amount.
+    pub fn amount(&self) -> Option<Arc<Amount>> {
+        self.0.amount.map(|amount| Arc::new(amount.into()))
+    }
+
+    /// Sets the requested payment amount and returns a new PjUri
+    pub fn set_amount(&self, amount: Arc<Amount>) -> Self {
+        let mut uri = self.0.clone();
+        uri.amount = Some((*amount).clone().into());
+        PjUri(uri, self.1.clone())
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.0.label.clone().and_then(|x| String::try_from(x).ok())
+    }
+
+    /// Sets the label and returns a new PjUri
+    pub fn set_label(&self, label: String) -> Self {
+        let mut uri = self.0.clone();
+        uri.label = Some(label.into());
+        PjUri(uri, self.1.clone())
+    }
+
+    pub fn message(&self) -> Option<String> {
+        self.0.message.clone().and_then(|x| String::try_from(x).ok())
     }
 
-    /// Sets the amount in sats and returns a new PjUri
-    pub fn set_amount_sats(&self, amount_sats: u64) -> Self {
+    /// Sets the message and returns a new PjUri
+    pub fn set_message(&self, message: String) -> Self {
         let mut uri = self.0.clone();
-        let amount = payjoin::bitcoin::Amount::from_sat(amount_sats);
-        uri.amount = Some(amount);
-        uri.into()
+        uri.message = Some(message.into());
+        PjUri(uri, self.1.clone())
     }
 
     pub fn pj_endpoint(&self) -> String {
         self.0.extras.endpoint().to_string()
     }
 
+    /// Whether the receiver may substitute the sender's payment output, from `pjos`.
+    pub fn output_substitution(&self) -> OutputSubstitution {
+        self.0.extras.output_substitution()
+    }
+
+    /// The directory's OHTTP keys from the `OH` parameter of a v2 `pj=` fragment.
+    pub fn ohttp_keys(&self) -> Option<Arc<OhttpKeys>> {
+        let keys = fragment_segment(self.0.extras.endpoint(), "OH")?;
+        payjoin::OhttpKeys::from_str(keys).ok().map(|keys| Arc::new(keys.into()))
+    }
+
+    /// When the receiver's session expires, in seconds since the Unix epoch.
+    ///
+    /// Returns `None` for v1 endpoints, which carry no expiration.
+    pub fn expires_at(&self) -> Option<u64> {
+        self.expiry().map(crate::persist::unix_secs)
+    }
+
+    /// The receiver's compressed public key from the `RK` parameter of a v2 `pj=` fragment.
+    pub fn receiver_pubkey(&self) -> Option<Vec<u8>> {
+        fragment_param(self.0.extras.endpoint(), "RK")
+    }
+
+    /// Query parameters that are not part of BIP 21 or payjoin, e.g. `lightning`.
+    pub fn extras(&self) -> HashMap<String, String> {
+        self.1.to_map()
+    }
+
+    /// A BOLT11 invoice from the `lightning` parameter.
+    pub fn lightning(&self) -> Option<String> {
+        self.1.get(LIGHTNING)
+    }
+
+    /// A BOLT12 offer from the `lno` parameter.
+    pub fn lno(&self) -> Option<String> {
+        self.1.get(LNO)
+    }
+
+    /// A silent payment address from the `sp` parameter.
+    pub fn sp(&self) -> Option<String> {
+        self.1.get(SP)
+    }
+
     pub fn as_string(&self) -> String {
-        self.0.clone().to_string()
+        self.1.append_to(self.0.clone().to_string())
+    }
+
+    /// The URI in a form that encodes into a smaller QR code.
+    ///
+    /// As BIP 21 and BIP 77 suggest, the scheme, a bech32 address, the `pj=` endpoint's scheme
+    /// and host, the mailbox path and fragment of a v2 endpoint, and payment method parameters
+    /// that decode as bech32 are uppercased so they fit the alphanumeric QR mode.
+    /// [`Uri::parse`] accepts this form.
+    pub fn as_qr_string(&self) -> String {
+        let address = self.0.address.to_string();
+        let address = if self.0.address.script_pubkey().is_witness_program() {
+            address.to_uppercase()
+        } else {
+            address
+        };
+        let uri = self.as_string();
+        let Some((_, query)) = uri.split_once('?') else {
+            return format!("BITCOIN:{address}");
+        };
+        let params: Vec<String> = query
+            .split('&')
+            .map(|param| {
+                match param.split_once('=') {
+                    Some(("pj", _)) => format!("pj={}", qr_endpoint(self.0.extras.endpoint())),
+                    Some((key, value)) if is_bech32_payment_method(key, value) => {
+                        format!("{key}={}", value.to_uppercase())
+                    }
+                    _ => param.to_string(),
+                }
+            })
+            .collect();
+        format!("BITCOIN:{address}?{}", params.join("&"))
+    }
+}
+
+impl PjUri {
+    /// The session expiration time encoded in the `EX` parameter of the `pj=` fragment.
+    ///
+    /// Returns `None` for v1 endpoints, which carry no expiration.
+    pub(crate) fn expiry(&self) -> Option<SystemTime> {
+        endpoint_expiry(self.0.extras.endpoint())
+    }
+}
+
+/// Builds a [`PjUri`] requesting a payment to a v1 `pj=` endpoint or a v2 receiver session.
+///
+/// Start from [`crate::receive::Receiver::pj_uri_builder`] to request a payment to a v2
+/// session.
+#[derive(Clone)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+pub struct PjUriBuilder {
+    address: payjoin::bitcoin::Address,
+    endpoint: payjoin::Url,
+    amount: Option<payjoin::bitcoin::Amount>,
+    label: Option<String>,
+    message: Option<String>,
+    output_substitution: OutputSubstitution,
+    extras: ExtraParams,
+}
+
+impl From<PjUri> for PjUriBuilder {
+    fn from(value: PjUri) -> Self {
+        let (uri, extras) = (value.0, value.1);
+        Self {
+            endpoint: uri.extras.endpoint().clone(),
+            output_substitution: uri.extras.output_substitution(),
+            label: uri.label.and_then(|x| String::try_from(x).ok()),
+            message: uri.message.and_then(|x| String::try_from(x).ok()),
+            amount: uri.amount,
+            address: uri.address,
+            extras,
+        }
     }
 }
 
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+impl PjUriBuilder {
+    /// Request a payment to `address` over the v1 `endpoint`.
+    #[cfg_attr(feature = "uniffi", uniffi::constructor)]
+    pub fn new(address: Arc<Address>, endpoint: Arc<Url>) -> Self {
+        Self::with_endpoint((*address).clone(), endpoint.0.clone())
+    }
+
+    /// Sets the requested payment amount.
+    pub fn amount(&self, amount: Arc<Amount>) -> Self {
+        Self { amount: Some((*amount).clone().into()), ..self.clone() }
+    }
+
+    pub fn label(&self, label: String) -> Self {
+        Self { label: Some(label), ..self.clone() }
+    }
+
+    pub fn message(&self, message: String) -> Self {
+        Self { message: Some(message), ..self.clone() }
+    }
+
+    /// Whether the receiver may substitute the sender's payment output. Enabled by default.
+    pub fn output_substitution(&self, output_substitution: OutputSubstitution) -> Self {
+        Self { output_substitution, ..self.clone() }
+    }
+
+    /// Offer a BOLT11 invoice as an alternative to an on-chain payment.
+    pub fn lightning(&self, invoice: String) -> Self {
+        self.with_extra(LIGHTNING, invoice)
+    }
+
+    /// Offer a BOLT12 offer as an alternative to an on-chain payment.
+    pub fn lno(&self, offer: String) -> Self {
+        self.with_extra(LNO, offer)
+    }
+
+    /// Offer a silent payment address as an alternative to the URI address.
+    pub fn sp(&self, address: String) -> Self {
+        self.with_extra(SP, address)
+    }
+
+    pub fn build(&self) -> Result<PjUri, PjParseError> {
+        let mut uri = format!(
+            "bitcoin:{}?pj={}",
+            self.address,
+            url::form_urlencoded::byte_serialize(self.endpoint.as_str().as_bytes())
+                .collect::<String>()
+        );
+        if matches!(self.output_substitution, OutputSubstitution::Disabled) {
+            uri.push_str("&pjos=0");
+        }
+        let mut uri = payjoin::Uri::from_str(&uri)?
+            .assume_checked()
+            .check_pj_supported()
+            .map_err(|_| PjParseError::MissingEndpoint)?;
+        uri.amount = self.amount;
+        uri.label = self.label.clone().map(Into::into);
+        uri.message = self.message.clone().map(Into::into);
+        Ok(PjUri(uri, self.extras.clone()))
+    }
+
+    /// Build a [`Uri`], for APIs that accept URIs with or without payjoin support.
+    pub fn build_uri(&self) -> Result<Uri, PjParseError> {
+        Uri::parse(self.build()?.as_string())
+    }
+}
+
+impl PjUriBuilder {
+    fn with_endpoint(address: Address, endpoint: payjoin::Url) -> Self {
+        Self {
+            address: address.into(),
+            endpoint,
+            amount: None,
+            label: None,
+            message: None,
+            output_substitution: OutputSubstitution::Enabled,
+            extras: ExtraParams::default(),
+        }
+    }
+
+    fn with_extra(&self, key: &str, value: String) -> Self {
+        let mut builder = self.clone();
+        builder.extras.set(key, value);
+        builder
+    }
+}
+
+/// Encode a `pj=` endpoint with as many QR alphanumeric characters as possible.
+///
+/// The scheme and host are case-insensitive, as are the mailbox path and bech32 fragment
+/// parameters of a v2 endpoint. Userinfo, and the path and query of a v1 endpoint, are left
+/// alone since they may be case-sensitive.
+fn qr_endpoint(endpoint: &payjoin::Url) -> String {
+    let mut encoded = format!("{}://", endpoint.scheme().to_uppercase());
+    if !endpoint.username().is_empty() || endpoint.password().is_some() {
+        encoded.push_str(&percent_encode(endpoint.username()));
+        if let Some(password) = endpoint.password() {
+            encoded.push(':');
+            encoded.push_str(&percent_encode(password));
+        }
+        encoded.push('@');
+    }
+    encoded.push_str(&endpoint.host_str().unwrap_or_default().to_uppercase());
+    if let Some(port) = endpoint.port() {
+        encoded.push_str(&format!(":{port}"));
+    }
+    if is_v2_endpoint(endpoint) {
+        encoded.push_str(&percent_encode_path(&endpoint.path().to_uppercase()));
+    } else {
+        encoded.push_str(&percent_encode_path(endpoint.path()));
+    }
+    if let Some(query) = endpoint.query() {
+        encoded.push_str("%3F");
+        encoded.push_str(&percent_encode(query));
+    }
+    if let Some(fragment) = endpoint.fragment() {
+        encoded.push_str("%23");
+        encoded.push_str(&percent_encode(&fragment.to_uppercase()));
+    }
+    encoded
+}
+
+/// The session expiration time encoded in the `EX` parameter of a v2 `pj=` endpoint.
+pub(crate) fn endpoint_expiry(endpoint: &payjoin::Url) -> Option<SystemTime> {
+    let bytes = fragment_param(endpoint, "EX")?;
+    let secs = u32::from_be_bytes(bytes.try_into().ok()?);
+    Some(UNIX_EPOCH + Duration::from_secs(secs.into()))
+}
+
+/// Decode the bech32 payload of the `pj=` fragment parameter with the given human readable part.
+fn fragment_param(endpoint: &payjoin::Url, hrp: &str) -> Option<Vec<u8>> {
+    let parsed = CheckedHrpstring::new::<NoChecksum>(fragment_segment(endpoint, hrp)?).ok()?;
+    Some(parsed.byte_iter().collect())
+}
+
+/// The `pj=` fragment parameter with the given human readable part, still bech32 encoded.
+fn fragment_segment<'a>(endpoint: &'a payjoin::Url, hrp: &str) -> Option<&'a str> {
+    endpoint.fragment()?.split(['-', '+']).find(|param| {
+        CheckedHrpstring::new::<NoChecksum>(param)
+            .is_ok_and(|parsed| parsed.hrp().as_str().eq_ignore_ascii_case(hrp))
+    })
+}
+
+const LIGHTNING: &str = "lightning";
+const LNO: &str = "lno";
+const SP: &str = "sp";
+
+/// Whether `value` is a BOLT11 invoice, BOLT12 offer or silent payment address that decodes as
+/// bech32, so its case does not matter.
+fn is_bech32_payment_method(key: &str, value: &str) -> bool {
+    if key.eq_ignore_ascii_case(LIGHTNING) {
+        CheckedHrpstring::new::<Bech32>(value).is_ok()
+    } else if key.eq_ignore_ascii_case(LNO) {
+        // Offers are bech32 encoded without a checksum
+        CheckedHrpstring::new::<NoChecksum>(value).is_ok()
+    } else if key.eq_ignore_ascii_case(SP) {
+        CheckedHrpstring::new::<Bech32m>(value).is_ok()
+    } else {
+        false
+    }
+}
+
+/// Parameters handled by `payjoin::Uri` itself, which matches their keys case-sensitively.
+const KNOWN_PARAMS: [&str; 5] = ["amount", "label", "message", "pj", "pjos"];
+
+/// The query parameters of a URI that `payjoin::Uri` ignores, in their original order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct ExtraParams(Vec<(String, String)>);
+
+impl ExtraParams {
+    fn parse(uri: &str) -> Self {
+        let Some((_, query)) = uri.split_once('?') else {
+            return Self::default();
+        };
+        let params = query.split('&').filter_map(|param| {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            if key.is_empty() || KNOWN_PARAMS.contains(&key) {
+                return None;
+            }
+            Some((key.to_string(), percent_decode(value)?))
+        });
+        Self(params.collect())
+    }
+
+    fn to_map(&self) -> HashMap<String, String> {
+        self.0.iter().cloned().collect()
+    }
+
+    /// Keys are matched case-insensitively, as QR codes often uppercase them.
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.clone())
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        self.0.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+        self.0.push((key.to_string(), value));
+    }
+
+    /// Append the parameters to the query of a serialized URI.
+    fn append_to(&self, mut uri: String) -> String {
+        for (key, value) in &self.0 {
+            uri.push(if uri.contains('?') { '&' } else { '?' });
+            uri.push_str(key);
+            uri.push('=');
+            uri.push_str(&percent_encode(value));
+        }
+        uri
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| {
+            match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{b:02X}"),
+            }
+        })
+        .collect()
+}
+
+/// Like [`percent_encode`], but keeps the `/` path separators.
+fn percent_encode_path(path: &str) -> String {
+    path.split('/').map(percent_encode).collect::<Vec<_>>().join("/")
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        if b != b'%' {
+            bytes.push(b);
+            continue;
+        }
+        let hex = rest.get(..2)?;
+        bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+        rest = &rest[2..];
+    }
+    String::from_utf8(bytes).ok()
+}
+
 impl From<payjoin::Url> for Url {
     fn from(value: payjoin::Url) -> Self {
         Self(value)
@@ -132,3 +607,298 @@ impl Url {
         self.0.to_string()
     }
 }
+
+#[cfg(test)]
+#[cfg(not(feature = "uniffi"))]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{receiver, OHTTP_KEYS, PAYEE_URI, RECEIVER_ADDRESS};
+
+    #[test]
+    fn amount_round_trips_through_btc_and_sats() {
+        let uri = Uri::parse(PAYEE_URI.to_string()).unwrap().check_pj_supported().unwrap();
+        assert!(uri.amount().is_none());
+
+        let uri = uri.set_amount(Arc::new(Amount::from_btc(0.01).unwrap()));
+        assert_eq!(uri.amount().unwrap().to_sat(), 1_000_000);
+        let parsed = Uri::parse(uri.as_string()).unwrap();
+        assert_eq!(parsed.amount().unwrap().to_btc(), 0.01);
+    }
+
+    #[test]
+    fn built_v1_uri_round_trips() {
+        let address = Address::new(RECEIVER_ADDRESS.to_string(), Network::Testnet).unwrap();
+        let endpoint = Url::parse("https://example.com/pj?id=1".to_string()).unwrap();
+        let uri = PjUriBuilder::new(Arc::new(address), Arc::new(endpoint))
+            .amount(Arc::new(Amount::from_sat(50_000)))
+            .label("Coffee & cake".to_string())
+            .message("Table 3".to_string())
+            .output_substitution(OutputSubstitution::Disabled)
+            .build()
+            .unwrap();
+        assert!(uri.as_string().contains("pjos=0"));
+
+        let parsed = Uri::parse(uri.as_string()).unwrap();
+        assert_eq!(parsed.address(), RECEIVER_ADDRESS);
+        assert_eq!(parsed.amount().unwrap().to_sat(), 50_000);
+        assert_eq!(parsed.label().as_deref(), Some("Coffee & cake"));
+        assert_eq!(parsed.message().as_deref(), Some("Table 3"));
+        let pj_uri = parsed.check_pj_supported().unwrap();
+        assert_eq!(pj_uri.pj_endpoint(), "https://example.com/pj?id=1");
+    }
+
+    #[test]
+    fn builder_keeps_v2_session_endpoint() {
+        let receiver = receiver();
+        let uri =
+            receiver.pj_uri_builder().amount(Arc::new(Amount::from_sat(1_000))).build().unwrap();
+        assert_eq!(uri.pj_endpoint(), receiver.pj_uri().pj_endpoint());
+        let parsed = Uri::parse(uri.as_string()).unwrap().check_pj_supported().unwrap();
+        assert_eq!(parsed.amount().unwrap().to_sat(), 1_000);
+        assert!(parsed.expiry().is_some());
+    }
+
+    #[test]
+    fn v2_parameters_are_exposed() {
+        let uri = receiver().pj_uri();
+        assert!(matches!(uri.output_substitution(), OutputSubstitution::Enabled));
+        assert_eq!(uri.receiver_pubkey().map(|key| key.len()), Some(33));
+        assert!(uri.expires_at().is_some());
+        let keys = OhttpKeys::from_string(OHTTP_KEYS.to_string()).unwrap();
+        assert_eq!(uri.ohttp_keys().as_deref(), Some(&keys));
+
+        let v1 = Uri::parse(format!("{PAYEE_URI}&pjos=0")).unwrap().check_pj_supported().unwrap();
+        assert!(matches!(v1.output_substitution(), OutputSubstitution::Disabled));
+        assert!(v1.ohttp_keys().is_none());
+        assert!(v1.expires_at().is_none());
+        assert!(v1.receiver_pubkey().is_none());
+    }
+
+    #[test]
+    fn extra_parameters_round_trip() {
+        let uri = Uri::parse(format!("{PAYEE_URI}&foo=bar%20baz&label=Shop")).unwrap();
+        assert_eq!(uri.extras(), HashMap::from([("foo".to_string(), "bar baz".to_string())]));
+
+        let pj_uri = uri
+            .check_pj_supported()
+            .unwrap()
+            .set_label("Coffee & cake".to_string())
+            .set_message("Table 3".to_string());
+        assert_eq!(pj_uri.extras(), uri.extras());
+        let parsed = Uri::parse(pj_uri.as_string()).unwrap();
+        assert_eq!(parsed.extras(), uri.extras());
+        assert_eq!(parsed.label().as_deref(), Some("Coffee & cake"));
+        assert_eq!(parsed.message().as_deref(), Some("Table 3"));
+    }
+
+    #[test]
+    fn uppercase_known_parameters_are_extras() {
+        let uri = Uri::parse(format!("{PAYEE_URI}&LABEL=Shop")).unwrap();
+        assert_eq!(uri.label(), None);
+        assert_eq!(uri.extras(), HashMap::from([("LABEL".to_string(), "Shop".to_string())]));
+    }
+
+    #[test]
+    fn unified_payment_methods_round_trip() {
+        let invoice = "lnbc10u1pj0fake";
+        let offer = "lno1qgsqvgnwgcg35z6ee2h3yczraddm72xrfua9uve2rlrm9deu7xyfzrc";
+        let sp = "sp1qqfake";
+        let uri = Uri::parse(format!(
+            "{PAYEE_URI}&LIGHTNING={}&Lno={offer}&sp=%73%70%31qqfake",
+            invoice.to_uppercase()
+        ))
+        .unwrap();
+        assert_eq!(uri.lightning(), Some(invoice.to_uppercase()));
+        assert_eq!(uri.lno().as_deref(), Some(offer));
+        assert_eq!(uri.sp().as_deref(), Some(sp));
+
+        let pj_uri = uri.check_pj_supported().unwrap();
+        assert_eq!(pj_uri.lno().as_deref(), Some(offer));
+        let parsed = Uri::parse(pj_uri.as_string()).unwrap();
+        assert_eq!(parsed.extras(), uri.extras());
+
+        let built = PjUriBuilder::from(pj_uri)
+            .lightning(invoice.to_string())
+            .sp("sp1qqother".to_string())
+            .build()
+            .unwrap();
+        assert_eq!(built.lightning().as_deref(), Some(invoice));
+        assert_eq!(built.lno().as_deref(), Some(offer));
+        let parsed = Uri::parse(built.as_string()).unwrap();
+        assert_eq!(parsed.lightning().as_deref(), Some(invoice));
+        assert_eq!(parsed.sp().as_deref(), Some("sp1qqother"));
+        assert_eq!(parsed.extras().len(), 3);
+    }
+
+    #[test]
+    fn qr_string_round_trips() {
+        let rk = "RK1Q0DJS3VVDXWQQTLQ8022QGXSX7ML9PHZ6EDSF6AKEWQG758JPS2EV";
+        let uri = format!(
+            "bitcoin:{RECEIVER_ADDRESS}?amount=0.00001&label=Coffee\
+             &pj=https://payjo.in/txjcgktkxluuz%23EX1WKV8CEC-{OHTTP_KEYS}-{rk}\
+             &lightning=lnbc10u1pj0fakeqnvpqcekhaxw0&lno=lno1notbech32\
+             &sp=tsp1qqfakesqqentpaymentacx97c"
+        );
+        let uri = Uri::parse(uri).unwrap().check_pj_supported().unwrap();
+        let qr = uri.as_qr_string();
+        // The offer is not bech32, so its case is kept
+        assert_eq!(
+            qr,
+            format!(
+                "BITCOIN:TB1Q6D3A2W975YNY0ASUVD9A67NER4NKS58FF0Q8G4?amount=0.00001&label=Coffee\
+                 &pj=HTTPS://PAYJO.IN/TXJCGKTKXLUUZ%23EX1WKV8CEC-{OHTTP_KEYS}-{rk}\
+                 &lightning=LNBC10U1PJ0FAKEQNVPQCEKHAXW0&lno=lno1notbech32\
+                 &sp=TSP1QQFAKESQQENTPAYMENTACX97C"
+            )
+        );
+
+        let parsed = Uri::parse(qr.clone()).unwrap().check_pj_supported().unwrap();
+        assert_eq!(parsed.as_qr_string(), qr);
+        assert_eq!(parsed.address(), uri.address());
+        assert_eq!(parsed.amount().map(|a| a.to_sat()), Some(1_000));
+        assert_eq!(parsed.label(), uri.label());
+        assert_eq!(
+            parsed.pj_endpoint(),
+            format!("https://payjo.in/TXJCGKTKXLUUZ#EX1WKV8CEC-{OHTTP_KEYS}-{rk}")
+        );
+        assert_eq!(parsed.receiver_pubkey(), uri.receiver_pubkey());
+        assert_eq!(parsed.expires_at(), uri.expires_at());
+        assert_eq!(parsed.ohttp_keys(), uri.ohttp_keys());
+        assert_eq!(parsed.lightning().unwrap(), "LNBC10U1PJ0FAKEQNVPQCEKHAXW0");
+        assert_eq!(parsed.lno().unwrap(), "lno1notbech32");
+        assert_eq!(parsed.sp().unwrap(), "TSP1QQFAKESQQENTPAYMENTACX97C");
+
+        // Values that fail their checksum are not bech32 either
+        let uri = Uri::parse(format!("{PAYEE_URI}&lightning=lnbc10u1pj0fake"))
+            .unwrap()
+            .check_pj_supported()
+            .unwrap();
+        assert_eq!(
+            uri.as_qr_string(),
+            "BITCOIN:2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK?pj=HTTPS://EXAMPLE.COM/pj\
+             &lightning=lnbc10u1pj0fake"
+        );
+
+        // Userinfo is kept as is
+        let v1 =
+            "bitcoin:2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK?pj=https://User:Pw@example.com:8443/pj";
+        let uri = Uri::parse(v1.to_string()).unwrap().check_pj_supported().unwrap();
+        let qr = uri.as_qr_string();
+        assert_eq!(
+            qr,
+            "BITCOIN:2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK?pj=HTTPS://User:Pw@EXAMPLE.COM:8443/pj"
+        );
+        let parsed = Uri::parse(qr).unwrap().check_pj_supported().unwrap();
+        assert_eq!(parsed.as_string(), uri.as_string());
+
+        // Base58 addresses and v1 endpoint paths are case-sensitive
+        let v1 = "bitcoin:2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK?pj=https://example.com/Pj";
+        let uri = Uri::parse(v1.to_string()).unwrap().check_pj_supported().unwrap();
+        let qr = uri.as_qr_string();
+        assert_eq!(qr, "BITCOIN:2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK?pj=HTTPS://EXAMPLE.COM/Pj");
+        let parsed = Uri::parse(qr).unwrap().check_pj_supported().unwrap();
+        assert_eq!(parsed.as_string(), uri.as_string());
+    }
+
+    fn parse_error(uri: &str) -> PjParseError {
+        match Uri::parse(uri.to_string()) {
+            Ok(_) => panic!("{uri} should not parse"),
+            Err(e) => e,
+        }
+    }
+
+    fn not_supported(uri: &str) -> PjNotSupported {
+        match Uri::parse(uri.to_string()).unwrap().check_pj_supported() {
+            Ok(_) => panic!("{uri} should not support payjoin"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn parse_errors_are_enumerated() {
+        let address = "2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK";
+        assert_eq!(parse_error(&format!("litecoin:{address}")), PjParseError::InvalidScheme);
+        assert_eq!(parse_error("bitcoin:notanaddress"), PjParseError::InvalidAddress);
+        assert_eq!(
+            parse_error(&format!("bitcoin:{address}?amount=abc")),
+            PjParseError::InvalidAmount
+        );
+        assert!(matches!(
+            parse_error(&format!("bitcoin:{address}?req-foo=bar")),
+            PjParseError::UnknownRequiredParameter { .. }
+        ));
+        assert_eq!(
+            parse_error(&format!("bitcoin:{address}?pj=https://example.com&pjos=2")),
+            PjParseError::InvalidOutputSubstitution
+        );
+        assert_eq!(
+            parse_error(&format!("bitcoin:{address}?pj=http://example.com")),
+            PjParseError::InsecureEndpoint
+        );
+        assert!(matches!(
+            parse_error(&format!("bitcoin:{address}?pj=https://a.example&pj=https://b.example")),
+            PjParseError::DuplicateParameter { param } if param.contains("pj")
+        ));
+        assert_eq!(
+            parse_error(&format!("bitcoin:{address}?pjos=0")),
+            PjParseError::MissingEndpoint
+        );
+        assert!(matches!(
+            parse_error(&format!("bitcoin:{address}?pj=not%20a%20url")),
+            PjParseError::InvalidEndpoint { .. }
+        ));
+    }
+
+    #[test]
+    fn uris_are_checked_against_the_network() {
+        use Network::*;
+
+        // Legacy testnet addresses are shared by testnet, signet and regtest
+        let addresses: &[(&str, &[Network])] = &[
+            ("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", &[Bitcoin]),
+            ("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", &[Bitcoin]),
+            ("bc1qqqqsyqcyq5rqwzqfpg9scrgwpugpzysn4v0345", &[Bitcoin]),
+            ("bc1pqqqsyqcyq5rqwzqfpg9scrgwpugpzysnzs23v9ccrydpk8qarc0sg5tmnz", &[Bitcoin]),
+            ("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", &[Testnet, Signet, Regtest]),
+            ("2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK", &[Testnet, Signet, Regtest]),
+            ("tb1q6d3a2w975yny0asuvd9a67ner4nks58ff0q8g4", &[Testnet, Signet]),
+            ("tb1pqqqsyqcyq5rqwzqfpg9scrgwpugpzysnzs23v9ccrydpk8qarc0slua5fd", &[Testnet, Signet]),
+            ("bcrt1qqqqsyqcyq5rqwzqfpg9scrgwpugpzysnard0ew", &[Regtest]),
+            ("bcrt1pqqqsyqcyq5rqwzqfpg9scrgwpugpzysnzs23v9ccrydpk8qarc0sj9hjuh", &[Regtest]),
+        ];
+        for (address, valid) in addresses {
+            let uri = format!("bitcoin:{address}?amount=0.001");
+            for network in [Bitcoin, Testnet, Signet, Regtest] {
+                let parsed = Uri::parse_for_network(uri.clone(), network);
+                let required = Uri::parse(uri.clone()).unwrap().require_network(network);
+                if valid.contains(&network) {
+                    assert_eq!(parsed.unwrap().address(), *address);
+                    assert!(required.is_ok());
+                } else {
+                    let expected =
+                        || PjParseError::WrongNetwork { address: address.to_string(), network };
+                    assert_eq!(parsed.err(), Some(expected()));
+                    assert_eq!(required.err(), Some(expected()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_reasons_are_enumerated() {
+        let address = "2MuyMrZHkbHbfjudmKUy45dU4P17pjG2szK";
+        assert_eq!(not_supported(&format!("bitcoin:{address}")), PjNotSupported::MissingEndpoint);
+        let endpoint = "https://example.com/ID%23RK1QQQQ-EX1QQQQ";
+        assert_eq!(
+            not_supported(&format!("bitcoin:{address}?pj={endpoint}")),
+            PjNotSupported::MissingOhttpKeys
+        );
+        let endpoint = "https://example.com/ID%23RK1QQQQ-OH1QQQQ";
+        assert_eq!(
+            not_supported(&format!("bitcoin:{address}?pj={endpoint}")),
+            PjNotSupported::MissingExpiration
+        );
+        // Endpoints without v2 parameters are v1 endpoints
+        assert!(Uri::parse(PAYEE_URI.to_string()).unwrap().check_pj_supported().is_ok());
+    }
+}
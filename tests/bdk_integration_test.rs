@@ -200,7 +200,7 @@ fn build_original_psbt(
     dbg!("adding recipient");
     builder
         .fee_rate(FeeRate::from_sat_per_kwu(2000.0))
-        .add_recipient(script, pj_uri.amount_sats().unwrap_or(100_000_000))
+        .add_recipient(script, pj_uri.amount().map(|amount| amount.to_sat()).unwrap_or(100_000_000))
         .fee_rate(FeeRate::from_sat_per_vb(5.0))
         .only_witness_utxo();
     dbg!("finishing");
@@ -223,7 +223,10 @@ mod v2 {
 
     use bdk::wallet::AddressIndex;
     use bitcoin_ffi::{Address, Network};
-    use payjoin_ffi::receive::{NewReceiver, PayjoinProposal, Receiver, UncheckedProposal};
+    use payjoin_ffi::receive::{
+        MaybeInputsOwned, MaybeInputsSeen, NewReceiver, OutputsUnknown, PayjoinProposal,
+        ProvisionalProposal, Receiver, UncheckedProposal, WantsInputs, WantsOutputs,
+    };
     use payjoin_ffi::send::{Sender, SenderBuilder};
     use payjoin_ffi::uri::Uri;
     use payjoin_ffi::{NoopPersister, Request};
@@ -237,6 +240,138 @@ mod v2 {
 
     #[tokio::test]
     async fn v2_to_v2_full_cycle() {
+        let mut services = TestServices::initialize().await.unwrap();
+        tokio::select!(
+        _ = services.take_ohttp_relay_handle()  => assert!(false, "Ohttp relay is long running"),
+        _ = services.take_directory_handle()  => assert!(false, "Directory server is long running"),
+        res = do_v2_send_receive(&services, handle_directory_proposal) => assert!(res.is_ok(), "v2 send receive failed: {:#?}", res)
+        );
+    }
+
+    #[tokio::test]
+    async fn v2_to_v2_full_cycle_resuming_each_typestate() {
+        let mut services = TestServices::initialize().await.unwrap();
+        tokio::select!(
+        _ = services.take_ohttp_relay_handle()  => assert!(false, "Ohttp relay is long running"),
+        _ = services.take_directory_handle()  => assert!(false, "Directory server is long running"),
+        res = do_v2_send_receive(&services, handle_resumed_directory_proposal) => assert!(res.is_ok(), "v2 send receive failed: {:#?}", res)
+        );
+    }
+
+    /// Run a v2 payjoin through the directory, letting `handle_proposal` take the receiver from
+    /// the Original PSBT to the Payjoin Proposal.
+    async fn do_v2_send_receive(
+        services: &TestServices,
+        handle_proposal: fn(Wallet, UncheckedProposal) -> PayjoinProposal,
+    ) -> Result<(), BoxError> {
+        let (sender, receiver, bitcoind) = init_sender_receiver_wallet();
+        let blockchain_client = restore_rpc_client(&bitcoind, &get_sender_descriptor());
+        let agent = services.http_agent();
+        let directory = services.directory_url();
+        services.wait_for_services_ready().await?;
+        let ohttp_keys = payjoin_ffi::io::fetch_ohttp_keys_with_cert(
+            services.ohttp_relay_url().as_str(),
+            directory.as_str(),
+            services.cert(),
+        )
+        .await?;
+
+        let address = receiver.get_address(AddressIndex::New);
+        let new_session = NewReceiver::new(
+            Address::new(address.to_string(), Network::Regtest).unwrap(),
+            directory.to_string(),
+            ohttp_keys,
+            None,
+        )?;
+        let receiver_token = new_session.persist(&mut NoopPersister)?;
+        let session = Receiver::load(receiver_token, &NoopPersister)?;
+        let ohttp_relay = services.ohttp_relay_url();
+        // Poll receive request
+        let (request, client_response) = session.extract_req(ohttp_relay.to_string())?;
+        let response = agent
+            .post(request.url.as_string())
+            .header("Content-Type", request.content_type)
+            .body(request.body)
+            .send()
+            .await?;
+        assert!(response.status().is_success());
+        let response_body =
+            session.process_res(&response.bytes().await?, &client_response).unwrap();
+        // No proposal yet since sender has not responded
+        assert!(response_body.is_none());
+
+        // **********************
+        // Inside the Sender:
+        // Create a funded PSBT (not broadcasted) to address with amount given in the pj_uri
+        let pj_uri =
+            Uri::parse(session.pj_uri().as_string()).unwrap().check_pj_supported().unwrap();
+        let psbt = build_original_psbt(&sender, &pj_uri)?;
+        println!("\nOriginal sender psbt: {:#?}", psbt.to_string());
+
+        let new_sender = SenderBuilder::new(psbt.to_string(), pj_uri)?.build_recommended(
+            payjoin_ffi::FeeRate::from_sat_per_kwu(
+                payjoin::bitcoin::FeeRate::BROADCAST_MIN.to_sat_per_kwu(),
+            ),
+        )?;
+        let sender_token = new_sender.persist(&mut NoopPersister)?;
+        let req_ctx = Sender::load(sender_token, &NoopPersister)?;
+        let (request, context) = req_ctx.extract_v2(ohttp_relay.to_owned().into())?;
+        let response = agent
+            .post(request.url.as_string())
+            .header("Content-Type", request.content_type)
+            .body(request.body.clone())
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        let send_ctx = context.process_response(&response.bytes().await?)?;
+
+        // **********************
+        // Inside the Receiver:
+
+        // GET fallback psbt
+        let (request, client_response) = session.extract_req(ohttp_relay.to_string())?;
+        let response = agent
+            .post(request.url.as_string())
+            .header("Content-Type", request.content_type)
+            .body(request.body)
+            .send()
+            .await?;
+        let proposal = session
+            .process_res(&response.bytes().await?, &client_response)?
+            .expect("proposal should exist");
+        let payjoin_proposal = handle_proposal(receiver, proposal);
+        let (request, client_response) = payjoin_proposal.extract_req(ohttp_relay.to_string())?;
+        let response = agent
+            .post(request.url.as_string())
+            .header("Content-Type", request.content_type)
+            .body(request.body)
+            .send()
+            .await?;
+        payjoin_proposal.process_res(&response.bytes().await?, &client_response)?;
+
+        // **********************
+        // Inside the Sender:
+        // Sender checks, signs, finalizes, extracts, and broadcasts
+        // Replay post fallback to get the response
+        let (Request { url, body, content_type, .. }, ohttp_ctx) =
+            send_ctx.extract_req(ohttp_relay.to_string())?;
+        let response = agent
+            .post(url.as_string())
+            .header("Content-Type", content_type)
+            .body(body)
+            .send()
+            .await?;
+        let checked_payjoin_proposal_psbt =
+            send_ctx.process_response(&response.bytes().await?, &ohttp_ctx)?.unwrap();
+        let payjoin_tx = extract_pj_tx(&sender, checked_payjoin_proposal_psbt.as_str())?;
+        blockchain_client.broadcast(payjoin_tx).unwrap();
+        Ok(())
+    }
+
+    #[cfg(feature = "transport")]
+    #[tokio::test]
+    async fn v2_to_v2_full_cycle_over_transport() {
         let mut services = TestServices::initialize().await.unwrap();
         tokio::select!(
         _ = services.take_ohttp_relay_handle()  => assert!(false, "Ohttp relay is long running"),
@@ -245,9 +380,10 @@ mod v2 {
         );
 
         async fn do_v2_send_receive(services: &TestServices) -> Result<(), BoxError> {
+            use payjoin_ffi::transport::{Transport, TransportConfig};
+
             let (sender, receiver, bitcoind) = init_sender_receiver_wallet();
             let blockchain_client = restore_rpc_client(&bitcoind, &get_sender_descriptor());
-            let agent = services.http_agent();
             let directory = services.directory_url();
             services.wait_for_services_ready().await?;
             let ohttp_keys = payjoin_ffi::io::fetch_ohttp_keys_with_cert(
@@ -256,6 +392,14 @@ mod v2 {
                 services.cert(),
             )
             .await?;
+            let transport = Transport::new(
+                Arc::new(services.ohttp_relay_url().into()),
+                TransportConfig {
+                    timeout_secs: Some(30),
+                    root_certificates: vec![services.cert()],
+                    ..Default::default()
+                },
+            )?;
 
             let address = receiver.get_address(AddressIndex::New);
             let new_session = NewReceiver::new(
@@ -266,83 +410,32 @@ mod v2 {
             )?;
             let receiver_token = new_session.persist(&mut NoopPersister)?;
             let session = Receiver::load(receiver_token, &NoopPersister)?;
-            let ohttp_relay = services.ohttp_relay_url();
-            // Poll receive request
-            let (request, client_response) = session.extract_req(ohttp_relay.to_string())?;
-            let response = agent
-                .post(request.url.as_string())
-                .header("Content-Type", request.content_type)
-                .body(request.body)
-                .send()
-                .await?;
-            assert!(response.status().is_success());
-            let response_body =
-                session.process_res(&response.bytes().await?, &client_response).unwrap();
             // No proposal yet since sender has not responded
-            assert!(response_body.is_none());
+            assert!(session.poll(&transport).await?.is_none());
 
             // **********************
             // Inside the Sender:
-            // Create a funded PSBT (not broadcasted) to address with amount given in the pj_uri
             let pj_uri =
                 Uri::parse(session.pj_uri().as_string()).unwrap().check_pj_supported().unwrap();
             let psbt = build_original_psbt(&sender, &pj_uri)?;
-            println!("\nOriginal sender psbt: {:#?}", psbt.to_string());
-
-            let new_sender = SenderBuilder::new(psbt.to_string(), pj_uri)?
-                .build_recommended(payjoin::bitcoin::FeeRate::BROADCAST_MIN.to_sat_per_kwu())?;
+            let new_sender = SenderBuilder::new(psbt.to_string(), pj_uri)?.build_recommended(
+                payjoin_ffi::FeeRate::from_sat_per_kwu(
+                    payjoin::bitcoin::FeeRate::BROADCAST_MIN.to_sat_per_kwu(),
+                ),
+            )?;
             let sender_token = new_sender.persist(&mut NoopPersister)?;
-            let req_ctx = Sender::load(sender_token, &NoopPersister)?;
-            let (request, context) = req_ctx.extract_v2(ohttp_relay.to_owned().into())?;
-            let response = agent
-                .post(request.url.as_string())
-                .header("Content-Type", request.content_type)
-                .body(request.body.clone())
-                .send()
-                .await
-                .unwrap();
-            assert!(response.status().is_success());
-            let send_ctx = context.process_response(&response.bytes().await?)?;
+            let send_ctx = Sender::load(sender_token, &NoopPersister)?.send(&transport).await?;
 
             // **********************
             // Inside the Receiver:
-
-            // GET fallback psbt
-            let (request, client_response) = session.extract_req(ohttp_relay.to_string())?;
-            let response = agent
-                .post(request.url.as_string())
-                .header("Content-Type", request.content_type)
-                .body(request.body)
-                .send()
-                .await?;
-            let proposal = session
-                .process_res(&response.bytes().await?, &client_response)?
-                .expect("proposal should exist");
+            let proposal = session.poll(&transport).await?.expect("proposal should exist");
             let payjoin_proposal = handle_directory_proposal(receiver, proposal);
-            let (request, client_response) =
-                payjoin_proposal.extract_req(ohttp_relay.to_string())?;
-            let response = agent
-                .post(request.url.as_string())
-                .header("Content-Type", request.content_type)
-                .body(request.body)
-                .send()
-                .await?;
-            payjoin_proposal.process_res(&response.bytes().await?, &client_response)?;
+            payjoin_proposal.send(&transport).await?;
 
             // **********************
             // Inside the Sender:
-            // Sender checks, signs, finalizes, extracts, and broadcasts
-            // Replay post fallback to get the response
-            let (Request { url, body, content_type, .. }, ohttp_ctx) =
-                send_ctx.extract_req(ohttp_relay.to_string())?;
-            let response = agent
-                .post(url.as_string())
-                .header("Content-Type", content_type)
-                .body(body)
-                .send()
-                .await?;
             let checked_payjoin_proposal_psbt =
-                send_ctx.process_response(&response.bytes().await?, &ohttp_ctx)?.unwrap();
+                send_ctx.poll(&transport).await?.expect("payjoin proposal should exist");
             let payjoin_tx = extract_pj_tx(&sender, checked_payjoin_proposal_psbt.as_str())?;
             blockchain_client.broadcast(payjoin_tx).unwrap();
             Ok(())
@@ -387,10 +480,73 @@ mod v2 {
             wants_inputs.contribute_inputs(vec![selected_outpoint]).unwrap().commit_inputs();
 
         let payjoin_proposal = provisional_proposal
-            .finalize_proposal(|psbt| process_psbt(&receiver, psbt), Some(10), Some(100))
+            .finalize_proposal(
+                |psbt| process_psbt(&receiver, psbt),
+                Some(payjoin_ffi::FeeRate::from_sat_per_vb(10).unwrap()),
+                Some(payjoin_ffi::FeeRate::from_sat_per_vb(100).unwrap()),
+            )
             .unwrap();
         payjoin_proposal
     }
+
+    /// Serialize a receiver typestate and resume from the JSON, as after a restart.
+    macro_rules! resume {
+        ($typestate:ident, $proposal:expr) => {
+            $typestate::from_json(&$proposal.to_json().unwrap()).unwrap()
+        };
+    }
+
+    /// Like [`handle_directory_proposal`], but resumes every typestate from its JSON before
+    /// taking the next step.
+    fn handle_resumed_directory_proposal(
+        receiver: Wallet,
+        proposal: UncheckedProposal,
+    ) -> PayjoinProposal {
+        let receiver = Arc::new(receiver);
+        let proposal = resume!(UncheckedProposal, proposal);
+        let proposal = resume!(MaybeInputsOwned, proposal.assume_interactive_receiver());
+        let proposal = resume!(
+            MaybeInputsSeen,
+            proposal
+                .check_inputs_not_owned(|script| is_script_owned(&receiver, script.clone()))
+                .expect("Receiver should not own any of the inputs")
+        );
+        let proposal = resume!(
+            OutputsUnknown,
+            proposal
+                .check_no_inputs_seen_before(|outpoint| mock_is_output_known(outpoint.clone()))
+                .unwrap()
+        );
+        let wants_outputs = resume!(
+            WantsOutputs,
+            proposal
+                .identify_receiver_outputs(|script| is_script_owned(&receiver, script.clone()))
+                .expect("Receiver should have at least one output")
+        );
+        let wants_inputs = resume!(WantsInputs, wants_outputs.commit_outputs());
+
+        let available_inputs = receiver
+            .list_unspent()
+            .into_iter()
+            .map(input_pair_from_local_utxo)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let selected_outpoint = wants_inputs
+            .try_preserving_privacy(available_inputs)
+            .expect("receiver input that avoids surveillance not found");
+        let wants_inputs =
+            resume!(WantsInputs, wants_inputs.contribute_inputs(vec![selected_outpoint]).unwrap());
+        let provisional_proposal = resume!(ProvisionalProposal, wants_inputs.commit_inputs());
+
+        let payjoin_proposal = provisional_proposal
+            .finalize_proposal(
+                |psbt| process_psbt(&receiver, psbt),
+                Some(payjoin_ffi::FeeRate::from_sat_per_vb(10).unwrap()),
+                Some(payjoin_ffi::FeeRate::from_sat_per_vb(100).unwrap()),
+            )
+            .unwrap();
+        resume!(PayjoinProposal, payjoin_proposal)
+    }
 }
 
 fn input_pair_from_local_utxo(utxo: LocalUtxo) -> Result<InputPair, BoxError> {